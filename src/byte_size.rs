@@ -0,0 +1,90 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use miette::miette;
+
+const UNITS: [(&str, u64); 4] = [
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+/// A size in bytes, like `512MiB` or `8G`.
+///
+/// Units are powers of 1024, so `1K`, `1KB`, and `1KiB` are all 1024 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| miette!("Expected a size like `512MiB` or `8G`, found `{s}`"))?;
+
+        let unit = unit.trim().to_ascii_uppercase();
+        let unit = unit
+            .strip_suffix("IB")
+            .or_else(|| unit.strip_suffix('B'))
+            .unwrap_or(&unit);
+        let multiplier = if unit.is_empty() {
+            1
+        } else {
+            UNITS
+                .iter()
+                .find_map(|(name, multiplier)| (*name == unit).then_some(*multiplier))
+                .ok_or_else(|| {
+                    miette!("Unknown size unit in `{s}`; valid units are `K`, `M`, `G`, and `T`")
+                })?
+        };
+
+        Ok(Self((number * multiplier as f64) as u64))
+    }
+}
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, multiplier) in UNITS {
+            if self.0 >= multiplier {
+                return write!(f, "{:.2} {name}iB", self.0 as f64 / multiplier as f64);
+            }
+        }
+        write!(f, "{} B", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!("1024".parse::<ByteSize>().unwrap(), ByteSize(1024));
+        assert_eq!("1024B".parse::<ByteSize>().unwrap(), ByteSize(1024));
+        assert_eq!("2K".parse::<ByteSize>().unwrap(), ByteSize(2048));
+        assert_eq!("512MiB".parse::<ByteSize>().unwrap(), ByteSize(512 << 20));
+        assert_eq!("8G".parse::<ByteSize>().unwrap(), ByteSize(8 << 30));
+        assert_eq!("8 gb".parse::<ByteSize>().unwrap(), ByteSize(8 << 30));
+        assert_eq!("1.5GiB".parse::<ByteSize>().unwrap(), ByteSize(3 << 29));
+        assert_eq!("1T".parse::<ByteSize>().unwrap(), ByteSize(1 << 40));
+
+        assert!("".parse::<ByteSize>().is_err());
+        assert!("G".parse::<ByteSize>().is_err());
+        assert!("8 puppies".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn test_display_byte_size() {
+        assert_eq!(ByteSize(512).to_string(), "512 B");
+        assert_eq!(ByteSize(3 << 29).to_string(), "1.50 GiB");
+        assert_eq!(ByteSize(512 << 20).to_string(), "512.00 MiB");
+    }
+}
@@ -1,16 +1,28 @@
 //! Command-line argument parser and argument access.
+use std::ffi::OsString;
 use std::time::Duration;
 
 use camino::Utf8PathBuf;
 use clap::builder::ValueParserFactory;
+use clap::CommandFactory;
+use clap::FromArgMatches;
 use clap::Parser;
+use clap::ValueHint;
 use clap_complete::Shell;
+use miette::IntoDiagnostic;
+use miette::WrapErr;
 use tracing_subscriber::fmt::format::FmtSpan;
 
+use crate::byte_size::ByteSize;
 use crate::clap::FmtSpanParserFactory;
 use crate::clap::RustBacktrace;
 use crate::clonable_command::ClonableCommand;
+use crate::config::ConfigFile;
+use crate::config::CONFIG_FILE_NAME;
+use crate::ghci::ErrorFileFormat;
+use crate::hooks::OnChangeHook;
 use crate::ignore::GlobMatcher;
+use crate::lsp::LspTarget;
 use crate::normal_path::NormalPath;
 
 /// Ghciwatch loads a GHCi session for a Haskell project and reloads it
@@ -45,6 +57,10 @@ use crate::normal_path::NormalPath;
 /// Don't reload for `README.md` files:
 ///
 ///     ghciwatch --reload-glob '!src/**/README.md'
+///
+/// Load options from the `tests` profile in `ghciwatch.toml`:
+///
+///     ghciwatch --profile tests
 #[allow(rustdoc::invalid_rust_codeblocks)]
 #[derive(Debug, Clone, Parser)]
 #[command(
@@ -78,34 +94,91 @@ pub struct Opts {
 
     /// A file to write compilation errors to.
     ///
-    /// The output format is compatible with `ghcid`'s `--outputfile` option.
-    #[arg(long, alias = "outputfile", alias = "errors")]
+    /// By default, the output format is compatible with `ghcid`'s `--outputfile` option. See
+    /// `--error-file-format` for other formats.
+    #[arg(long, value_hint = ValueHint::FilePath, alias = "outputfile", alias = "errors")]
     pub error_file: Option<Utf8PathBuf>,
 
+    /// The format to write the `--error-file` in.
+    #[arg(long, default_value = "ghcid", requires = "error_file")]
+    pub error_file_format: ErrorFileFormat,
+
     /// A file to write GHCi stdout output to.
     ///
     /// Output will be duplicated to both the console/TUI and this file.
     /// This is especially useful when paired with "allow-eval" for diverting test output.
-    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    #[arg(short = 'o', long = "output", value_name = "FILE", value_hint = ValueHint::FilePath)]
     pub output_file: Option<Utf8PathBuf>,
 
     /// Evaluate Haskell code in comments.
     ///
     /// This parses line commands starting with `-- $>` or multiline commands delimited by `{- $>`
-    /// and `<$ -}` and evaluates them after reloads.
+    /// and `<$ -}` and evaluates them after reloads. Only the commands in modules which were
+    /// changed or recompiled are evaluated; use the `eval-all` control socket command or the `e`
+    /// key in the TUI to evaluate all of them.
     #[arg(long, alias = "allow-eval")]
     pub enable_eval: bool,
 
+    /// Write the results of eval commands back into the source files.
+    ///
+    /// After evaluation, each eval command's output is written as `-- <$` lines directly after
+    /// it, replacing any that are already there. Files are only rewritten if they haven't changed
+    /// since their eval commands were read, and the writes don't trigger reloads.
+    #[arg(long, requires = "enable_eval")]
+    pub write_eval_results: bool,
+
+    /// Evaluate Haddock examples like `-- >>> expr` as eval commands.
+    ///
+    /// The comment lines after an example, up to a blank comment line or the next example, are
+    /// its expected output, and are checked like `-- <$` lines.
+    #[arg(long, requires = "enable_eval")]
+    pub eval_doctests: bool,
+
+    /// Interrupt eval commands which run for longer than this duration, like `10s`.
+    ///
+    /// When a command times out, GHCi is interrupted with `SIGINT` and the next command is run.
+    /// This applies to eval comments and to `eval` commands sent to the control socket.
+    #[arg(long, value_name = "DURATION", value_parser = crate::clap::DurationValueParser::default())]
+    pub eval_timeout: Option<Duration>,
+
     /// Clear the screen before reloads and restarts.
     #[arg(long)]
     pub clear: bool,
 
-    /// Don't interrupt reloads when files change.
+    /// Don't interrupt reloads, eval commands, or tests when files change.
     ///
     /// Depending on your workflow, `ghciwatch` may feel more responsive with this set.
     #[arg(long)]
     pub no_interrupt_reloads: bool,
 
+    /// Restart the GHCi session if it crashes, up to this many times in a row.
+    ///
+    /// GHCi is restarted with an exponential backoff, so a session which crashes immediately
+    /// (because of a bad `.ghci` file or a missing package, for example) isn't restarted in a
+    /// tight loop. Once the limit is reached, `ghciwatch` stops restarting GHCi until the next
+    /// file change.
+    ///
+    /// By default, `ghciwatch` exits when GHCi crashes. If GHCi is found to have crashed when
+    /// sending it a command, it's restarted once, without waiting.
+    #[arg(long, value_name = "N")]
+    pub max_crash_restarts: Option<u32>,
+
+    /// Restart the GHCi session when it uses more than this much memory, like `8G` or `512MiB`.
+    ///
+    /// The resident memory of the GHCi process and its children is checked after each reload, and
+    /// the session is restarted once the reload finishes. This requires `/proc`, so it only works
+    /// on Linux.
+    #[arg(long, value_name = "SIZE")]
+    pub max_ghci_memory: Option<ByteSize>,
+
+    /// Show progress while compiling modules.
+    ///
+    /// Progress is shown as a single updating status line with the number of modules compiled,
+    /// the module being compiled, the elapsed time, and an estimate of the time remaining. In TUI
+    /// mode, it's shown as a progress bar.
+    #[arg(long)]
+    pub progress: bool,
+
     /// Enable TUI mode (experimental).
     #[arg(long, hide = true, env = "GHCIWATCH_TUI")]
     pub tui: bool,
@@ -121,6 +194,65 @@ pub struct Opts {
     #[arg(long, env = "GHCIWATCH_TRACK_WARNINGS")]
     pub track_warnings: bool,
 
+    /// Explain why modules were recompiled after each reload.
+    ///
+    /// This shows a tree from each changed file to the recompiled modules which import it,
+    /// directly or indirectly. The import graph is approximated by reading the `import` lines
+    /// in each recompiled module's source.
+    #[arg(long)]
+    pub explain_recompilation: bool,
+
+    /// Serve diagnostics to editors with the Language Server Protocol.
+    ///
+    /// Give `stdio` to talk to a single editor over `stdin` and `stdout` (GHCi output is written to
+    /// `stderr` instead), or a path to listen for any number of editors on a Unix socket.
+    ///
+    /// Only `textDocument/publishDiagnostics` is supported; diagnostics are published after every
+    /// reload, restart, and startup.
+    #[arg(long, value_name = "stdio|SOCKET_PATH", value_hint = ValueHint::FilePath)]
+    pub lsp: Option<LspTarget>,
+
+    /// Listen for commands on a Unix socket at the given path.
+    ///
+    /// Clients send one JSON object per line, like `{"command": "reload"}`, and receive one JSON
+    /// object per line in response, including the resulting compilation log.
+    ///
+    /// Supported commands are `reload`, `restart`, `test`, `toggle-track-warnings`,
+    /// `toggle-no-load`, `eval` (with an `expression` field), `eval-all`, and `quit`. Responses to
+    /// `eval` include the `output` GHCi printed.
+    #[arg(long, value_name = "SOCKET_PATH", value_hint = ValueHint::FilePath)]
+    pub control_socket: Option<Utf8PathBuf>,
+
+    /// Write a stream of session lifecycle events to the given path, one JSON object per line.
+    ///
+    /// Events include startup, the start of reloads and restarts, compiled modules and how long
+    /// they took to compile, diagnostics, compilation summaries, hooks starting and finishing, test
+    /// results, and the slowest modules to compile on shutdown. Every event has a `version` field;
+    /// unlike `--log-json`, this format is stable within a version.
+    ///
+    /// If the path is a Unix socket, `ghciwatch` connects to it and writes events there.
+    /// Otherwise, the file is truncated and events are written to it.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub event_stream: Option<Utf8PathBuf>,
+
+    /// A configuration file to load options from.
+    ///
+    /// Keys are the long names of command-line options, like `watch = ["src", "test"]` or
+    /// `track-warnings = true`. Relative paths are resolved relative to the configuration file's
+    /// directory. Options given on the command line take precedence.
+    ///
+    /// By default, `ghciwatch` looks for a `ghciwatch.toml` file in the current directory and its
+    /// parents.
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, env = "GHCIWATCH_CONFIG")]
+    pub config: Option<Utf8PathBuf>,
+
+    /// A named profile to apply from the configuration file.
+    ///
+    /// Profiles are tables under `[profiles.NAME]` in the configuration file, and their values
+    /// replace the top-level values for the same options.
+    #[arg(long, env = "GHCIWATCH_PROFILE")]
+    pub profile: Option<String>,
+
     /// Generate Markdown CLI documentation.
     #[cfg(feature = "clap-markdown")]
     #[arg(long, hide = true)]
@@ -176,7 +308,7 @@ pub struct WatchOpts {
     /// A path to watch for changes.
     ///
     /// Directories are watched recursively. Can be given multiple times.
-    #[arg(long = "watch", value_name = "PATH")]
+    #[arg(long = "watch", value_name = "PATH", value_hint = ValueHint::AnyPath)]
     pub paths: Vec<NormalPath>,
 
     /// Reload the GHCi session when paths matching this glob change.
@@ -205,6 +337,30 @@ pub struct WatchOpts {
     /// [1]: https://gitlab.haskell.org/ghc/ghc/-/issues/11596
     #[arg(long = "restart-glob")]
     pub restart_globs: Vec<String>,
+
+    /// Run a shell command before reloading when paths matching a glob change.
+    ///
+    /// Given as `GLOB=SHELL_CMD`, like `--on-change '**/*.proto=make bindings'`. Globs have the
+    /// same semantics as `--reload-glob`. The matching paths are passed to the command in the
+    /// `GHCIWATCH_CHANGED_PATHS` environment variable, separated by newlines.
+    ///
+    /// Commands support the same prefixes as shell lifecycle hooks, like `async:` and
+    /// `timeout=30s:`.
+    ///
+    /// Can be given multiple times.
+    #[arg(long = "on-change", value_name = "GLOB=SHELL_CMD")]
+    pub on_change: Vec<OnChangeHook>,
+
+    /// Ignore filesystem events caused by shell hooks for paths matching this glob.
+    ///
+    /// Changes to matching paths made while a synchronous shell lifecycle hook or `--on-change`
+    /// command is running (or shortly after it finishes) won't trigger reloads or restarts. This
+    /// keeps hooks which generate files, like `hpack`, from triggering themselves in a loop.
+    /// Changes to other paths while a hook is running are handled as usual.
+    ///
+    /// Globs have the same semantics as `--reload-glob`. Can be given multiple times.
+    #[arg(long = "ignore-hook-writes", value_name = "GLOB")]
+    pub ignore_hook_writes: Vec<String>,
 }
 
 impl WatchOpts {
@@ -217,6 +373,19 @@ impl WatchOpts {
     pub fn restart_globs(&self) -> miette::Result<GlobMatcher> {
         GlobMatcher::from_globs(self.restart_globs.iter())
     }
+
+    /// Build the specified globs into a matcher.
+    pub fn ignore_hook_writes(&self) -> miette::Result<GlobMatcher> {
+        GlobMatcher::from_globs(self.ignore_hook_writes.iter())
+    }
+
+    /// Build the globs for each `--on-change` hook into a matcher.
+    pub fn on_change_hooks(&self) -> miette::Result<Vec<(GlobMatcher, OnChangeHook)>> {
+        self.on_change
+            .iter()
+            .map(|hook| Ok((GlobMatcher::from_globs([&hook.glob])?, hook.clone())))
+            .collect()
+    }
 }
 
 // TODO: Possibly set `RUST_LIB_BACKTRACE` from `RUST_BACKTRACE` as well, so that `full`
@@ -261,7 +430,7 @@ pub struct LoggingOpts {
     /// Path to write JSON logs to.
     ///
     /// JSON logs are not yet stable and the format may change on any release.
-    #[arg(long, value_name = "PATH")]
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
     pub log_json: Option<Utf8PathBuf>,
 }
 
@@ -368,6 +537,49 @@ impl std::str::FromStr for TuiAction {
 }
 
 impl Opts {
+    /// Parse the command-line arguments, adding options from the configuration file, if any.
+    ///
+    /// Exits the process if the command-line arguments are invalid.
+    pub fn parse_with_config() -> miette::Result<Self> {
+        let args = std::env::args_os().collect::<Vec<_>>();
+        let command = Self::command();
+        let matches = command.clone().get_matches_from(&args);
+        let opts = Self::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+        let path = match &opts.config {
+            Some(path) => path.clone(),
+            None => match ConfigFile::find(&crate::current_dir_utf8()?) {
+                Some(path) => path,
+                None => {
+                    if let Some(profile) = &opts.profile {
+                        return Err(miette::miette!(
+                            "`--profile {profile}` was given, but no `{CONFIG_FILE_NAME}` was found"
+                        ));
+                    }
+                    return Ok(opts);
+                }
+            },
+        };
+
+        let config = ConfigFile::load(&path)?;
+        let config_args = config.to_args(opts.profile.as_deref(), &command, &matches)?;
+
+        // Options from the configuration file go before the command-line arguments, after the
+        // program name.
+        let mut args = args.into_iter();
+        let args = args
+            .next()
+            .into_iter()
+            .chain(config_args.into_iter().map(OsString::from))
+            .chain(args);
+
+        let mut opts = Self::try_parse_from(args)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Invalid options in {}", config.path()))?;
+        opts.config = Some(path);
+        Ok(opts)
+    }
+
     /// Perform late initialization of the command-line arguments. If `init` isn't called before
     /// the arguments are used, the behavior is undefined.
     pub fn init(&mut self) -> miette::Result<()> {
@@ -377,6 +589,12 @@ impl Opts {
             self.watch.paths.push(NormalPath::from_cwd("src")?);
         }
 
+        if self.tui && self.lsp == Some(LspTarget::Stdio) {
+            return Err(miette::miette!(
+                "`--lsp stdio` can't be used with `--tui`, which also writes to stdout"
+            ));
+        }
+
         // These help our libraries (particularly `color-eyre`) see these options.
         // The options are provided mostly for documentation.
         std::env::set_var("RUST_BACKTRACE", self.logging.backtrace.to_string());
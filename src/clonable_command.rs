@@ -104,6 +104,25 @@ impl ClonableCommand {
         self
     }
 
+    /// Set an environment variable for this command. See [`StdCommand::env`].
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env
+            .get_or_insert_with(Default::default)
+            .insert(key.into(), Some(value.into()));
+        self
+    }
+
+    /// Set environment variables for this command. See [`StdCommand::envs`].
+    pub fn envs(
+        mut self,
+        vars: impl IntoIterator<Item = (impl Into<OsString>, impl Into<OsString>)>,
+    ) -> Self {
+        for (key, value) in vars {
+            self = self.env(key, value);
+        }
+        self
+    }
+
     /// Create a new [`std::process::Command`] from this command's configuration.
     pub fn as_std(&self) -> StdCommand {
         let mut ret = StdCommand::new(&self.program);
@@ -0,0 +1,352 @@
+//! Project configuration files (`ghciwatch.toml`).
+//!
+//! Keys in the configuration file are the long names of command-line options, and values are
+//! strings, numbers, booleans, or arrays of those for options which can be given multiple times:
+//!
+//! ```toml
+//! command = "cabal v2-repl lib:test-dev"
+//! watch = ["src", "test"]
+//! track-warnings = true
+//!
+//! [profiles.tests]
+//! test-ghci = "TestMain.testMain"
+//! ```
+//!
+//! Values in a profile replace the top-level values for the same option. Options given on the
+//! command line or through environment variables replace the values in the configuration file.
+//!
+//! Relative paths (for options with a path [`ValueHint`]) are resolved relative to the directory
+//! containing the configuration file, so they don't depend on where `ghciwatch` is run from.
+
+use std::collections::BTreeMap;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use clap::parser::ValueSource;
+use clap::ArgAction;
+use clap::ArgMatches;
+use clap::Command;
+use clap::ValueHint;
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use crate::lsp::LspTarget;
+
+/// The name of the configuration file to search for.
+pub const CONFIG_FILE_NAME: &str = "ghciwatch.toml";
+
+/// The key in the configuration file which holds named profiles.
+const PROFILES_KEY: &str = "profiles";
+
+/// Options which can't be set in the configuration file.
+const FORBIDDEN_KEYS: &[&str] = &["config", "profile"];
+
+/// A parsed configuration file.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: Utf8PathBuf,
+    table: toml::Table,
+}
+
+impl ConfigFile {
+    /// Find a configuration file in the given directory or any of its parents.
+    pub fn find(start: &Utf8Path) -> Option<Utf8PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Read and parse a configuration file.
+    pub fn load(path: &Utf8Path) -> miette::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to read {path}"))?;
+        Self::parse(path, &contents)
+    }
+
+    fn parse(path: &Utf8Path, contents: &str) -> miette::Result<Self> {
+        let table = contents
+            .parse::<toml::Table>()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to parse {path}"))?;
+        Ok(Self {
+            path: path.to_owned(),
+            table,
+        })
+    }
+
+    /// The path this configuration file was loaded from.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Get the values from this configuration file, with the given profile applied.
+    fn values(&self, profile: Option<&str>) -> miette::Result<BTreeMap<&str, &toml::Value>> {
+        let mut values = self
+            .table
+            .iter()
+            .filter(|(key, _)| *key != PROFILES_KEY)
+            .map(|(key, value)| (key.as_str(), value))
+            .collect::<BTreeMap<_, _>>();
+
+        if let Some(profile) = profile {
+            let profiles = match self.table.get(PROFILES_KEY) {
+                Some(toml::Value::Table(profiles)) => profiles,
+                Some(_) => {
+                    return Err(miette!("`{PROFILES_KEY}` must be a table in {}", self.path));
+                }
+                None => {
+                    return Err(miette!("No profiles are defined in {}", self.path));
+                }
+            };
+
+            match profiles.get(profile) {
+                Some(toml::Value::Table(table)) => {
+                    values.extend(table.iter().map(|(key, value)| (key.as_str(), value)));
+                }
+                Some(_) => {
+                    return Err(miette!(
+                        "Profile `{profile}` must be a table in {}",
+                        self.path
+                    ));
+                }
+                None => {
+                    return Err(miette!(
+                        "No profile named `{profile}` in {}; available profiles are: {}",
+                        self.path,
+                        profiles
+                            .keys()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Convert the values in this configuration file into command-line arguments for the given
+    /// `command`.
+    ///
+    /// Options which were given in `matches` (on the command line or through environment
+    /// variables) are skipped, so that they take precedence over the configuration file.
+    pub fn to_args(
+        &self,
+        profile: Option<&str>,
+        command: &Command,
+        matches: &ArgMatches,
+    ) -> miette::Result<Vec<String>> {
+        let mut args = Vec::new();
+
+        for (key, value) in self.values(profile)? {
+            if FORBIDDEN_KEYS.contains(&key) {
+                return Err(miette!(
+                    "Option `{key}` can't be set in a configuration file"
+                ));
+            }
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(key))
+                .ok_or_else(|| miette!("Unknown option `{key}` in {}", self.path))?;
+
+            if let Some(ValueSource::CommandLine | ValueSource::EnvVariable) =
+                matches.value_source(arg.get_id().as_str())
+            {
+                continue;
+            }
+
+            let action = arg.get_action();
+            if !action.takes_values() {
+                match (action, value) {
+                    (ArgAction::SetTrue, toml::Value::Boolean(true)) => {
+                        args.push(format!("--{key}"));
+                    }
+                    (ArgAction::SetTrue, toml::Value::Boolean(false)) => {}
+                    (ArgAction::SetTrue, _) => {
+                        return Err(miette!("Option `{key}` must be a boolean in {}", self.path));
+                    }
+                    _ => {
+                        return Err(miette!(
+                            "Option `{key}` can't be set in a configuration file"
+                        ));
+                    }
+                }
+                continue;
+            }
+
+            let values = match value {
+                toml::Value::Array(values) => {
+                    if !matches!(action, ArgAction::Append) && values.len() != 1 {
+                        return Err(miette!(
+                            "Option `{key}` can only be given once, but {} has {} values for it",
+                            self.path,
+                            values.len()
+                        ));
+                    }
+                    values.iter().collect()
+                }
+                value => vec![value],
+            };
+
+            let is_path = matches!(
+                arg.get_value_hint(),
+                ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath
+            );
+
+            for value in values {
+                let value = match value {
+                    toml::Value::String(value) if is_path => self.resolve_path(key, value),
+                    toml::Value::String(value) => value.clone(),
+                    toml::Value::Integer(value) => value.to_string(),
+                    toml::Value::Float(value) => value.to_string(),
+                    toml::Value::Boolean(value) => value.to_string(),
+                    toml::Value::Datetime(value) => value.to_string(),
+                    toml::Value::Array(_) | toml::Value::Table(_) => {
+                        return Err(miette!(
+                            "Option `{key}` must be a string, number, boolean, or array of those in {}",
+                            self.path
+                        ));
+                    }
+                };
+                args.push(format!("--{key}={value}"));
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// Resolve a relative path relative to the directory containing this configuration file.
+    fn resolve_path(&self, key: &str, value: &str) -> String {
+        if key == "lsp" && matches!(value.parse(), Ok(LspTarget::Stdio)) {
+            return value.to_owned();
+        }
+        match self.path.parent() {
+            Some(dir) if Utf8Path::new(value).is_relative() => dir.join(value).into_string(),
+            _ => value.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::CommandFactory;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    use crate::cli::Opts;
+
+    fn to_args(contents: &str, profile: Option<&str>, cli: &[&str]) -> miette::Result<Vec<String>> {
+        let command = Opts::command();
+        let matches = command
+            .clone()
+            .try_get_matches_from(std::iter::once("ghciwatch").chain(cli.iter().copied()))
+            .into_diagnostic()?;
+        ConfigFile::parse(&Utf8Path::new("/puppy").join(CONFIG_FILE_NAME), contents)?
+            .to_args(profile, &command, &matches)
+    }
+
+    const CONFIG: &str = indoc!(
+        r#"
+        command = "cabal v2-repl lib:test-dev"
+        watch = ["src", "test"]
+        track-warnings = true
+        clear = false
+        debounce = "1s"
+        after-startup-ghci = [":set -Wall"]
+
+        [profiles.tests]
+        test-ghci = "TestMain.testMain"
+        watch = ["test"]
+        "#
+    );
+
+    #[test]
+    fn test_to_args() {
+        assert_eq!(
+            to_args(CONFIG, None, &[]).unwrap(),
+            vec![
+                "--after-startup-ghci=:set -Wall",
+                "--command=cabal v2-repl lib:test-dev",
+                "--debounce=1s",
+                "--track-warnings",
+                "--watch=/puppy/src",
+                "--watch=/puppy/test",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_args_profile() {
+        assert_eq!(
+            to_args(CONFIG, Some("tests"), &[]).unwrap(),
+            vec![
+                "--after-startup-ghci=:set -Wall",
+                "--command=cabal v2-repl lib:test-dev",
+                "--debounce=1s",
+                "--test-ghci=TestMain.testMain",
+                "--track-warnings",
+                "--watch=/puppy/test",
+            ]
+        );
+
+        assert!(to_args(CONFIG, Some("puppy"), &[]).is_err());
+    }
+
+    #[test]
+    fn test_to_args_command_line_overrides() {
+        assert_eq!(
+            to_args(CONFIG, None, &["--watch", "lib", "--command", "ghci"]).unwrap(),
+            vec![
+                "--after-startup-ghci=:set -Wall",
+                "--debounce=1s",
+                "--track-warnings",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_args_paths() {
+        assert_eq!(
+            to_args(
+                indoc!(
+                    r#"
+                    error-file = "ghcid.txt"
+                    event-stream = "/tmp/events.jsonl"
+                    lsp = "stdio"
+                    watch = ["../lib"]
+                    "#
+                ),
+                None,
+                &[]
+            )
+            .unwrap(),
+            vec![
+                "--error-file=/puppy/ghcid.txt",
+                "--event-stream=/tmp/events.jsonl",
+                "--lsp=stdio",
+                "--watch=/puppy/../lib",
+            ]
+        );
+
+        assert_eq!(
+            to_args("lsp = \"ghciwatch.sock\"", None, &[]).unwrap(),
+            vec!["--lsp=/puppy/ghciwatch.sock"]
+        );
+    }
+
+    #[test]
+    fn test_to_args_errors() {
+        assert!(to_args("puppy = 1", None, &[]).is_err());
+        assert!(to_args("profile = \"tests\"", None, &[]).is_err());
+        assert!(to_args("version = true", None, &[]).is_err());
+        assert!(to_args("track-warnings = \"yes\"", None, &[]).is_err());
+        assert!(to_args("command = [\"ghci\", \"cabal repl\"]", None, &[]).is_err());
+        assert!(to_args("watch = [[\"src\"]]", None, &[]).is_err());
+    }
+}
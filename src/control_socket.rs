@@ -0,0 +1,249 @@
+//! A Unix socket for controlling a running `ghciwatch` session.
+//!
+//! Clients send one JSON request per line and receive one JSON response per line:
+//!
+//! ```text
+//! > {"command": "reload"}
+//! < {"ok": true, "log": {"summary": {"result": "ok", ...}, "diagnostics": [], ...}}
+//! > {"command": "eval", "expression": ":type id"}
+//! < {"ok": true, "log": {"summary": null, ...}, "output": "id :: a -> a\n"}
+//! > {"command": "bogus"}
+//! < {"ok": false, "error": "unknown variant `bogus`, ..."}
+//! ```
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::UnixListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::instrument;
+
+use crate::ghci::manager::ControlCommand;
+use crate::ghci::manager::ControlOutput;
+use crate::ghci::manager::ControlReply;
+use crate::ghci::manager::InternalCommand;
+use crate::ghci::manager::WatcherEvent;
+use crate::ghci::GhciCommand;
+use crate::shutdown::ShutdownHandle;
+
+/// A request read from the control socket.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+enum Request {
+    /// Reload all modules.
+    Reload,
+    /// Restart the `ghci` session.
+    Restart,
+    /// Run the test hooks.
+    Test,
+    /// Toggle warning tracking.
+    ToggleTrackWarnings,
+    /// Toggle `--repl-no-load`.
+    ToggleNoLoad,
+    /// Run every eval command.
+    EvalAll,
+    /// Evaluate an expression or `ghci` command.
+    Eval { expression: String },
+    /// Shut down `ghciwatch`.
+    Quit,
+}
+
+impl Request {
+    /// Convert this request into a command for the `ghci` session, or `None` for [`Request::Quit`].
+    fn into_command(self) -> Option<ControlCommand> {
+        Some(match self {
+            Request::Reload => ControlCommand::Reload,
+            Request::Restart => ControlCommand::Restart,
+            Request::Test => ControlCommand::Test,
+            Request::ToggleTrackWarnings => {
+                ControlCommand::Internal(InternalCommand::ToggleTrackWarnings)
+            }
+            Request::ToggleNoLoad => ControlCommand::Internal(InternalCommand::ToggleNoLoad),
+            Request::EvalAll => ControlCommand::Internal(InternalCommand::EvalAll),
+            Request::Eval { expression } => ControlCommand::Eval(GhciCommand(expression)),
+            Request::Quit => {
+                return None;
+            }
+        })
+    }
+}
+
+/// Listen for connections on the control socket at `path` and forward their commands to the
+/// `ghci` session.
+#[instrument(level = "debug", skip(handle, sender))]
+pub async fn run_control_socket(
+    mut handle: ShutdownHandle,
+    path: Utf8PathBuf,
+    sender: mpsc::Sender<WatcherEvent>,
+) -> miette::Result<()> {
+    let listener = bind(&path)?;
+    tracing::info!(%path, "Listening for commands on control socket");
+
+    let mut connections = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            _ = handle.on_shutdown_requested() => {
+                break;
+            }
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((stream, _address)) => {
+                        let (reader, writer) = stream.into_split();
+                        connections.spawn(serve_client(
+                            reader,
+                            writer,
+                            sender.clone(),
+                            handle.clone(),
+                        ));
+                    }
+                    Err(err) => {
+                        tracing::debug!("Failed to accept control socket connection: {err}");
+                    }
+                }
+            }
+            Some(result) = connections.join_next() => {
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => tracing::debug!("Control socket connection failed: {err:?}"),
+                    Err(err) => tracing::debug!("Control socket connection task failed: {err}"),
+                }
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+/// Bind a Unix socket at the given path, removing a stale socket if one exists.
+fn bind(path: &Utf8Path) -> miette::Result<UnixListener> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to remove stale control socket {path}"))?;
+    }
+    UnixListener::bind(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to listen for commands on {path}"))
+}
+
+/// Respond to requests from a single client until it disconnects.
+async fn serve_client<R, W>(
+    reader: R,
+    mut writer: W,
+    sender: mpsc::Sender<WatcherEvent>,
+    handle: ShutdownHandle,
+) -> miette::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await.into_diagnostic()? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = serde_json::from_str::<Request>(&line);
+        tracing::debug!(?request, "Got control socket request");
+        let response = match request {
+            Ok(request) => match request.into_command() {
+                Some(command) => send_command(&sender, command).await,
+                None => {
+                    let _ = handle.request_shutdown();
+                    json!({"ok": true})
+                }
+            },
+            Err(err) => error_response(err),
+        };
+
+        let mut response = response.to_string();
+        response.push('\n');
+        writer
+            .write_all(response.as_bytes())
+            .await
+            .into_diagnostic()
+            .wrap_err("Failed to write control socket response")?;
+    }
+
+    Ok(())
+}
+
+/// Send a command to the `ghci` session and wait for the result.
+async fn send_command(sender: &mpsc::Sender<WatcherEvent>, command: ControlCommand) -> Value {
+    let (reply, receiver) = ControlReply::new();
+
+    if sender
+        .send(WatcherEvent::Control {
+            requests: vec![(command, reply)],
+        })
+        .await
+        .is_err()
+    {
+        return error_response("ghci event channel closed");
+    }
+
+    match receiver.await {
+        Ok(Ok(ControlOutput { log, output })) => {
+            let mut response = json!({"ok": true, "log": log});
+            if let Some(output) = output {
+                response["output"] = output.into();
+            }
+            response
+        }
+        Ok(Err(err)) => error_response(err),
+        Err(_) => error_response(miette!(
+            "Command was dropped before it finished, probably because it was interrupted"
+        )),
+    }
+}
+
+fn error_response(err: impl ToString) -> Value {
+    json!({"ok": false, "error": err.to_string()})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "reload"}"#).unwrap(),
+            Request::Reload
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "toggle-track-warnings"}"#).unwrap(),
+            Request::ToggleTrackWarnings
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "eval", "expression": "1 + 1"}"#)
+                .unwrap(),
+            Request::Eval {
+                expression: "1 + 1".into()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "eval-all"}"#).unwrap(),
+            Request::EvalAll
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "eval"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"command": "puppy"}"#).is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Serialize;
+
+use crate::ghci::compile_times::ModuleTime;
 use crate::ghci::parse::CompilationResult;
 use crate::ghci::parse::CompilationSummary;
 use crate::ghci::parse::CompilingModule;
@@ -6,11 +9,14 @@ use crate::ghci::parse::GhcMessage;
 use crate::ghci::parse::Severity;
 
 /// A log of messages from compilation, used to write the error log.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CompilationLog {
     pub summary: Option<CompilationSummary>,
     pub diagnostics: Vec<GhcDiagnostic>,
     pub compiled_modules: Vec<CompilingModule>,
+    /// How long each compiled module took to compile.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub module_times: Vec<ModuleTime>,
 }
 
 impl CompilationLog {
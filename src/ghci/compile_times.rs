@@ -0,0 +1,328 @@
+//! Per-module compile times, measured from when each `[n of m] Compiling` line is printed.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use super::event_stream::serialize_millis;
+use super::event_stream::Event;
+use super::event_stream::EventStream;
+use super::progress::compiling_line;
+
+/// How many modules to show in the slowest modules report.
+pub const SLOWEST_MODULES_COUNT: usize = 10;
+
+/// How long a module took to compile.
+///
+/// This is measured from when the module's `Compiling` line is printed to when the next module's
+/// line (or the prompt) is printed, so it's approximate when modules are compiled in parallel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleTime {
+    /// The module's fully-qualified name.
+    pub name: String,
+    /// How long the module took to compile.
+    #[serde(serialize_with = "serialize_millis")]
+    pub duration_ms: Duration,
+}
+
+/// Compile times for a module over the whole session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ModuleStats {
+    /// How many times the module was compiled.
+    pub count: u32,
+    /// The average compile time.
+    #[serde(serialize_with = "serialize_millis")]
+    pub average_ms: Duration,
+    /// The longest compile time.
+    #[serde(serialize_with = "serialize_millis")]
+    pub max_ms: Duration,
+    /// The total time spent compiling the module.
+    #[serde(skip)]
+    total: Duration,
+}
+
+impl ModuleStats {
+    fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.average_ms = self.total / self.count;
+        self.max_ms = self.max_ms.max(duration);
+    }
+}
+
+/// A module in the slowest modules report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SlowModule {
+    /// The module's fully-qualified name.
+    pub name: String,
+    /// The module's compile times.
+    #[serde(flatten)]
+    pub stats: ModuleStats,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// The module currently being compiled and when it started.
+    current: Option<(String, Instant)>,
+    /// Modules compiled since the last prompt.
+    compiled: Vec<ModuleTime>,
+    /// Compile times for every module compiled this session.
+    history: HashMap<String, ModuleStats>,
+    /// Was compilation interrupted before the next prompt?
+    interrupted: bool,
+}
+
+impl Inner {
+    /// Record the compile time of the module currently being compiled, if any, and return it.
+    fn finish_current(&mut self, now: Instant) -> Option<ModuleTime> {
+        self.current.take().map(|(name, start)| {
+            let duration = now.saturating_duration_since(start);
+            tracing::trace!(module = %name, ?duration, "Compiled module");
+            self.history
+                .entry(name.clone())
+                .or_default()
+                .record(duration);
+            let time = ModuleTime {
+                name,
+                duration_ms: duration,
+            };
+            self.compiled.push(time.clone());
+            time
+        })
+    }
+}
+
+/// A clonable handle to the compile times for a session.
+///
+/// Modules are written to the event stream as they start and finish compiling.
+#[derive(Debug, Clone, Default)]
+pub struct CompileTimes {
+    inner: Arc<Mutex<Inner>>,
+    events: EventStream,
+}
+
+impl CompileTimes {
+    /// Track compile times, writing them to the given event stream.
+    pub fn new(events: EventStream) -> Self {
+        Self {
+            inner: Default::default(),
+            events,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("Compile times mutex was poisoned")
+    }
+
+    /// Observe a line of `ghci` output, starting the timer for a module if it's a `[n of m]
+    /// Compiling` line.
+    pub fn line(&self, line: &str) {
+        if let Some((_index, _total, module)) = compiling_line(line) {
+            self.start_module(module.name.clone(), Instant::now());
+            self.events.queue(Event::Compiling(module));
+        }
+    }
+
+    fn start_module(&self, name: String, now: Instant) {
+        let mut inner = self.lock();
+        if let Some(time) = inner.finish_current(now) {
+            self.events.queue(Event::Compiled(time));
+        }
+        inner.current = Some((name, now));
+    }
+
+    /// Note that compilation has finished, returning how long each module took to compile since
+    /// the last call.
+    pub fn finish(&self) -> Vec<ModuleTime> {
+        self.finish_at(Instant::now())
+    }
+
+    fn finish_at(&self, now: Instant) -> Vec<ModuleTime> {
+        let mut inner = self.lock();
+        if std::mem::take(&mut inner.interrupted) {
+            // The module being compiled didn't finish, so its time isn't meaningful.
+            if let Some((name, _start)) = inner.current.take() {
+                tracing::trace!(module = %name, "Discarding compile time for interrupted module");
+            }
+        } else if let Some(time) = inner.finish_current(now) {
+            self.events.queue(Event::Compiled(time));
+        }
+        std::mem::take(&mut inner.compiled)
+    }
+
+    /// Note that compilation was interrupted, so the module being compiled when the next prompt
+    /// is printed should be discarded rather than recorded.
+    pub fn interrupt(&self) {
+        self.lock().interrupted = true;
+    }
+
+    /// Get the modules with the longest average compile times, slowest first.
+    pub fn slowest(&self, count: usize) -> Vec<SlowModule> {
+        let inner = self.lock();
+        let mut modules = inner
+            .history
+            .iter()
+            .map(|(name, stats)| SlowModule {
+                name: name.clone(),
+                stats: *stats,
+            })
+            .collect::<Vec<_>>();
+        modules.sort_by(|a, b| {
+            b.stats
+                .average_ms
+                .cmp(&a.stats.average_ms)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        modules.truncate(count);
+        modules
+    }
+
+    /// Format a report of the slowest modules, or `None` if no modules have been compiled.
+    pub fn report(&self, count: usize) -> Option<String> {
+        let slowest = self.slowest(count);
+        if slowest.is_empty() {
+            return None;
+        }
+        let mut report = String::from("Slowest modules (average, max, times compiled):");
+        for module in slowest {
+            write!(
+                report,
+                "\n{:>9.2?} {:>9.2?} {:>4}  {}",
+                module.stats.average_ms, module.stats.max_ms, module.stats.count, module.name
+            )
+            .expect("Writing to a `String` never fails");
+        }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_compile_times() {
+        let times = CompileTimes::default();
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+
+        times.start_module("A".to_owned(), secs(0));
+        times.start_module("B".to_owned(), secs(1));
+        assert_eq!(
+            times.finish_at(secs(4)),
+            vec![
+                ModuleTime {
+                    name: "A".to_owned(),
+                    duration_ms: Duration::from_secs(1),
+                },
+                ModuleTime {
+                    name: "B".to_owned(),
+                    duration_ms: Duration::from_secs(3),
+                },
+            ]
+        );
+        assert_eq!(times.finish_at(secs(5)), vec![]);
+
+        times.start_module("A".to_owned(), secs(10));
+        times.finish_at(secs(15));
+
+        let slowest = times.slowest(SLOWEST_MODULES_COUNT);
+        assert_eq!(
+            slowest
+                .iter()
+                .map(|module| (
+                    module.name.as_str(),
+                    module.stats.count,
+                    module.stats.average_ms,
+                    module.stats.max_ms
+                ))
+                .collect::<Vec<_>>(),
+            // Modules with the same average are sorted by name.
+            vec![
+                ("A", 2, Duration::from_secs(3), Duration::from_secs(5)),
+                ("B", 1, Duration::from_secs(3), Duration::from_secs(3)),
+            ]
+        );
+        assert_eq!(times.slowest(1).len(), 1);
+    }
+
+    #[test]
+    fn test_compile_times_interrupted() {
+        let times = CompileTimes::default();
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+
+        times.start_module("A".to_owned(), secs(0));
+        times.interrupt();
+        times.start_module("B".to_owned(), secs(1));
+        assert_eq!(
+            times.finish_at(secs(2)),
+            vec![ModuleTime {
+                name: "A".to_owned(),
+                duration_ms: Duration::from_secs(1),
+            }]
+        );
+        assert_eq!(
+            times
+                .slowest(SLOWEST_MODULES_COUNT)
+                .iter()
+                .map(|module| module.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["A"]
+        );
+
+        // Only the next prompt is affected.
+        times.start_module("B".to_owned(), secs(3));
+        assert_eq!(times.finish_at(secs(4)).len(), 1);
+    }
+
+    #[test]
+    fn test_report() {
+        let times = CompileTimes::default();
+        assert_eq!(times.report(SLOWEST_MODULES_COUNT), None);
+
+        times.line("[1 of 1] Compiling MyLib ( src/MyLib.hs, interpreted )");
+        times.finish();
+        let report = times.report(SLOWEST_MODULES_COUNT).unwrap();
+        assert!(report.starts_with("Slowest modules"));
+        assert!(report.ends_with("   1  MyLib"));
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = camino::Utf8PathBuf::try_from(dir.path().join("events.jsonl")).unwrap();
+        let events = EventStream::open(&path).await.unwrap();
+        let times = CompileTimes::new(events.clone());
+
+        times.line("[1 of 2] Compiling A ( src/A.hs, interpreted )");
+        times.line("[2 of 2] Compiling B ( src/B.hs, interpreted )");
+        times.finish();
+        // Wait for the queued events to be written.
+        events.emit(Event::Startup).await;
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written
+                .lines()
+                .map(|line| {
+                    let event: serde_json::Value = serde_json::from_str(line).unwrap();
+                    format!("{} {}", event["event"], event["name"])
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                r#""compiling" "A""#,
+                r#""compiled" "A""#,
+                r#""compiling" "B""#,
+                r#""compiled" "B""#,
+                r#""startup" null"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Structured JSON output for the error log.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "sequence": 3,
+//!   "timestamp": "2024-01-01T00:00:00.000Z",
+//!   "result": "err",
+//!   "modules_loaded": {"count": 2},
+//!   "diagnostics": [
+//!     {
+//!       "path": "src/MyModule.hs",
+//!       "start": {"line": 4, "column": 11},
+//!       "end": {"line": 4, "column": 19},
+//!       "severity": "error",
+//!       "code": "GHC-83865",
+//!       "flags": [],
+//!       "message": "[GHC-83865]\n    • Couldn't match type ..."
+//!     }
+//!   ]
+//! }
+//! ```
+
+use std::time::SystemTime;
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::ghci::parse::GhcDiagnostic;
+use crate::ghci::CompilationLog;
+
+/// The version of the JSON error log format.
+const VERSION: u32 = 1;
+
+/// Render a compilation log as JSON.
+///
+/// The `sequence` number increases with every write, so consumers can tell whether the file has
+/// been rewritten since they last read it.
+pub fn json_log(log: &CompilationLog, sequence: u64, now: SystemTime) -> Value {
+    json!({
+        "version": VERSION,
+        "sequence": sequence,
+        "timestamp": humantime::format_rfc3339_millis(now).to_string(),
+        "result": log.summary.map(|summary| summary.result),
+        "modules_loaded": log.summary.map(|summary| summary.modules_loaded),
+        "diagnostics": log.diagnostics.iter().map(json_diagnostic).collect::<Vec<_>>(),
+    })
+}
+
+fn json_diagnostic(diagnostic: &GhcDiagnostic) -> Value {
+    let (start, end) = if diagnostic.span.is_zero() {
+        (Value::Null, Value::Null)
+    } else {
+        (json!(diagnostic.span.start()), json!(diagnostic.span.end()))
+    };
+
+    json!({
+        "path": diagnostic.path,
+        "start": start,
+        "end": end,
+        "severity": diagnostic.severity,
+        "code": diagnostic.code,
+        "flags": diagnostic.flags,
+        "message": diagnostic.message.trim(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::ghci::parse::CompilationResult;
+    use crate::ghci::parse::CompilationSummary;
+    use crate::ghci::parse::DiagnosticCode;
+    use crate::ghci::parse::ModulesLoaded;
+    use crate::ghci::parse::PositionRange;
+    use crate::ghci::parse::Severity;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_json_log() {
+        let log = CompilationLog {
+            summary: Some(CompilationSummary {
+                result: CompilationResult::Err,
+                modules_loaded: ModulesLoaded::Count(2),
+            }),
+            diagnostics: vec![
+                GhcDiagnostic {
+                    severity: Severity::Error,
+                    path: Some("src/MyModule.hs".into()),
+                    span: PositionRange::new(4, 11, 4, 19),
+                    message: " [GHC-83865]\n    • Couldn't match type\n".into(),
+                    code: Some(DiagnosticCode::new(83865)),
+                    ..Default::default()
+                },
+                GhcDiagnostic {
+                    severity: Severity::Warning,
+                    path: None,
+                    span: Default::default(),
+                    message: " [-Wmissing-home-modules]\n    Modules are missing\n".into(),
+                    flags: vec!["-Wmissing-home-modules".into()],
+                    ..Default::default()
+                },
+            ],
+            compiled_modules: vec![],
+            module_times: vec![],
+        };
+
+        assert_eq!(
+            json_log(
+                &log,
+                3,
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+            ),
+            json!({
+                "version": 1,
+                "sequence": 3,
+                "timestamp": "2023-11-14T22:13:20.000Z",
+                "result": "err",
+                "modules_loaded": {"count": 2},
+                "diagnostics": [
+                    {
+                        "path": "src/MyModule.hs",
+                        "start": {"line": 4, "column": 11},
+                        "end": {"line": 4, "column": 19},
+                        "severity": "error",
+                        "code": "GHC-83865",
+                        "flags": [],
+                        "message": "[GHC-83865]\n    • Couldn't match type",
+                    },
+                    {
+                        "path": null,
+                        "start": null,
+                        "end": null,
+                        "severity": "warning",
+                        "code": null,
+                        "flags": ["-Wmissing-home-modules"],
+                        "message": "[-Wmissing-home-modules]\n    Modules are missing",
+                    },
+                ],
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use camino::Utf8Path;
 use camino::Utf8PathBuf;
 use miette::IntoDiagnostic;
@@ -10,18 +12,40 @@ use super::parse::CompilationResult;
 use super::parse::ModulesLoaded;
 use super::CompilationLog;
 
+mod json;
+mod sarif;
+
+/// The format to write the error log in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFileFormat {
+    /// `ghcid`-compatible text, for `ghcid` plugins in your editor of choice.
+    #[default]
+    Ghcid,
+    /// SARIF 2.1.0 JSON, for code-review and static analysis tools.
+    Sarif,
+    /// Structured JSON, including a sequence number and timestamp for each write.
+    Json,
+}
+
 /// Error log writer.
 ///
-/// This produces `ghcid`-compatible output, which can be consumed by `ghcid` plugins in your
-/// editor of choice.
+/// By default, this produces `ghcid`-compatible output, which can be consumed by `ghcid` plugins
+/// in your editor of choice.
 pub struct ErrorLog {
     path: Option<Utf8PathBuf>,
+    format: ErrorFileFormat,
+    /// The number of times the error log has been written.
+    sequence: u64,
 }
 
 impl ErrorLog {
     /// Construct a new error log writer for the given path.
-    pub fn new(path: Option<Utf8PathBuf>) -> Self {
-        Self { path }
+    pub fn new(path: Option<Utf8PathBuf>, format: ErrorFileFormat) -> Self {
+        Self {
+            path,
+            format,
+            sequence: 0,
+        }
     }
 
     /// Get the path for this error log writer, if any.
@@ -40,9 +64,40 @@ impl ErrorLog {
             }
         };
 
+        self.sequence += 1;
+
         let file = File::create(path).await.into_diagnostic()?;
         let mut writer = BufWriter::new(file);
 
+        match self.format {
+            ErrorFileFormat::Ghcid => Self::write_ghcid(&mut writer, path, log).await?,
+            ErrorFileFormat::Sarif => {
+                tracing::debug!(%path, "Writing SARIF log");
+                let root = crate::current_dir_utf8()?;
+                let sarif =
+                    serde_json::to_vec_pretty(&sarif::sarif_log(log, &root)).into_diagnostic()?;
+                writer.write_all(&sarif).await.into_diagnostic()?;
+            }
+            ErrorFileFormat::Json => {
+                tracing::debug!(%path, sequence = self.sequence, "Writing JSON log");
+                let json = json::json_log(log, self.sequence, SystemTime::now());
+                let json = serde_json::to_vec_pretty(&json).into_diagnostic()?;
+                writer.write_all(&json).await.into_diagnostic()?;
+            }
+        }
+
+        // This is load-bearing! If we don't properly flush/shutdown the handle, nothing gets
+        // written!
+        writer.shutdown().await.into_diagnostic()?;
+
+        Ok(())
+    }
+
+    async fn write_ghcid(
+        writer: &mut BufWriter<File>,
+        path: &Utf8Path,
+        log: &CompilationLog,
+    ) -> miette::Result<()> {
         if let Some(summary) = log.summary {
             // `ghcid` only writes the headline if there's no errors.
             if let CompilationResult::Ok = summary.result {
@@ -67,10 +122,6 @@ impl ErrorLog {
                 .into_diagnostic()?;
         }
 
-        // This is load-bearing! If we don't properly flush/shutdown the handle, nothing gets
-        // written!
-        writer.shutdown().await.into_diagnostic()?;
-
         Ok(())
     }
 }
@@ -0,0 +1,216 @@
+//! [SARIF 2.1.0][sarif] output for the error log.
+//!
+//! [sarif]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use std::collections::BTreeSet;
+
+use camino::Utf8Path;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::ghci::parse::CompilationResult;
+use crate::ghci::parse::GhcDiagnostic;
+use crate::ghci::parse::Severity;
+use crate::ghci::CompilationLog;
+use crate::lsp::file_uri;
+
+/// The URI base ID that relative paths are resolved against.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// Render a compilation log as a SARIF log with a single run.
+///
+/// Relative paths are resolved against `root`, which should be the directory `ghci` runs in.
+pub fn sarif_log(log: &CompilationLog, root: &Utf8Path) -> Value {
+    let rules = log
+        .diagnostics
+        .iter()
+        .filter_map(rule)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|(id, help_uri)| {
+            let mut rule = json!({ "id": id });
+            if let Some(help_uri) = help_uri {
+                rule["helpUri"] = help_uri.into();
+            }
+            rule
+        })
+        .collect::<Vec<_>>();
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": "ghciwatch",
+                "version": env!("CARGO_PKG_VERSION"),
+                "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                "rules": rules,
+            },
+        },
+        "originalUriBaseIds": {
+            SRCROOT: {
+                "uri": format!("{}/", file_uri(root).trim_end_matches('/')),
+            },
+        },
+        // GHC's columns count characters, not UTF-16 code units.
+        "columnKind": "unicodeCodePoints",
+        "results": log.diagnostics.iter().map(sarif_result).collect::<Vec<_>>(),
+    });
+
+    if let Some(result) = log.result() {
+        run["invocations"] = json!([{
+            "executionSuccessful": result == CompilationResult::Ok,
+        }]);
+    }
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+fn sarif_result(diagnostic: &GhcDiagnostic) -> Value {
+    let mut result = json!({
+        "level": match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        },
+        "message": {
+            "text": diagnostic.message.trim(),
+        },
+    });
+
+    if let Some((id, _)) = rule(diagnostic) {
+        result["ruleId"] = id.into();
+    }
+
+    if let Some(path) = &diagnostic.path {
+        let artifact_location = if path.is_absolute() {
+            json!({ "uri": file_uri(path) })
+        } else {
+            json!({ "uri": path.as_str(), "uriBaseId": SRCROOT })
+        };
+        let mut physical_location = json!({ "artifactLocation": artifact_location });
+        if !diagnostic.span.is_zero() {
+            let start = diagnostic.span.start();
+            let end = diagnostic.span.end();
+            physical_location["region"] = json!({
+                "startLine": start.line(),
+                "startColumn": start.column(),
+                "endLine": end.line(),
+                // GHC's end columns are inclusive, SARIF's are exclusive.
+                "endColumn": end.column() + 1,
+            });
+        }
+        result["locations"] = json!([{ "physicalLocation": physical_location }]);
+    }
+
+    result
+}
+
+/// Get a rule ID and help URI for a diagnostic.
+///
+/// The ID is the diagnostic code (like `GHC-88464`) if there is one, or otherwise the warning flag
+/// (like `-Wunused-imports`). Only diagnostic codes have a help URI.
+fn rule(diagnostic: &GhcDiagnostic) -> Option<(String, Option<String>)> {
+    match (&diagnostic.code, diagnostic.flags.first()) {
+        (Some(code), _) => Some((code.to_string(), Some(code.url()))),
+        (None, Some(flag)) => Some((flag.clone(), None)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ghci::parse::CompilationSummary;
+    use crate::ghci::parse::DiagnosticCode;
+    use crate::ghci::parse::ModulesLoaded;
+    use crate::ghci::parse::PositionRange;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_sarif_log() {
+        let log = CompilationLog {
+            summary: Some(CompilationSummary {
+                result: CompilationResult::Err,
+                modules_loaded: ModulesLoaded::Count(1),
+            }),
+            diagnostics: vec![
+                GhcDiagnostic {
+                    severity: Severity::Error,
+                    path: Some("src/MyModule.hs".into()),
+                    span: PositionRange::new(4, 11, 4, 19),
+                    message: " [GHC-83865]\n    • Couldn't match type\n".into(),
+                    code: Some(DiagnosticCode::new(83865)),
+                    ..Default::default()
+                },
+                GhcDiagnostic {
+                    severity: Severity::Warning,
+                    path: None,
+                    span: Default::default(),
+                    message: " [-Wmissing-home-modules]\n    Modules are missing\n".into(),
+                    flags: vec!["-Wmissing-home-modules".into()],
+                    ..Default::default()
+                },
+            ],
+            compiled_modules: vec![],
+            module_times: vec![],
+        };
+
+        assert_eq!(
+            sarif_log(&log, Utf8Path::new("/home/puppy/my project")),
+            json!({
+                "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+                "version": "2.1.0",
+                "runs": [{
+                    "tool": {
+                        "driver": {
+                            "name": "ghciwatch",
+                            "version": env!("CARGO_PKG_VERSION"),
+                            "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                            "rules": [
+                                {"id": "-Wmissing-home-modules"},
+                                {
+                                    "id": "GHC-83865",
+                                    "helpUri": "https://errors.haskell.org/messages/GHC-83865",
+                                },
+                            ],
+                        },
+                    },
+                    "originalUriBaseIds": {
+                        "%SRCROOT%": {"uri": "file:///home/puppy/my%20project/"},
+                    },
+                    "columnKind": "unicodeCodePoints",
+                    "invocations": [{"executionSuccessful": false}],
+                    "results": [
+                        {
+                            "ruleId": "GHC-83865",
+                            "level": "error",
+                            "message": {"text": "[GHC-83865]\n    • Couldn't match type"},
+                            "locations": [{
+                                "physicalLocation": {
+                                    "artifactLocation": {
+                                        "uri": "src/MyModule.hs",
+                                        "uriBaseId": "%SRCROOT%",
+                                    },
+                                    "region": {
+                                        "startLine": 4,
+                                        "startColumn": 11,
+                                        "endLine": 4,
+                                        "endColumn": 20,
+                                    },
+                                },
+                            }],
+                        },
+                        {
+                            "ruleId": "-Wmissing-home-modules",
+                            "level": "warning",
+                            "message": {"text": "[-Wmissing-home-modules]\n    Modules are missing"},
+                        },
+                    ],
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! A machine-readable stream of session lifecycle events.
+//!
+//! Each event is written as a single line of JSON:
+//!
+//! ```text
+//! {"version":1,"timestamp":"2024-01-01T00:00:00.000Z","event":"reload-start","paths":["src/MyLib.hs"]}
+//! {"version":1,"timestamp":"2024-01-01T00:00:00.123Z","event":"compiling","name":"MyLib","path":"src/MyLib.hs"}
+//! {"version":1,"timestamp":"2024-01-01T00:00:00.450Z","event":"compiled","name":"MyLib","duration_ms":327}
+//! {"version":1,"timestamp":"2024-01-01T00:00:00.456Z","event":"summary","result":"ok","modules_loaded":{"count":1},"duration_ms":456}
+//! ```
+//!
+//! Unlike `--log-json`, this format is stable; breaking changes will increment the `version`.
+
+use std::fmt::Debug;
+use std::os::unix::fs::FileTypeExt;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+
+use super::compile_times::ModuleTime;
+use super::compile_times::SlowModule;
+use super::parse::CompilationResult;
+use super::parse::CompilingModule;
+use super::parse::GhcDiagnostic;
+use super::parse::ModulesLoaded;
+
+/// The version of the event stream format.
+const VERSION: u32 = 1;
+
+/// A lifecycle event written to the event stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A `ghci` session is starting, either at `ghciwatch` startup or for a restart.
+    Startup,
+    /// A reload is starting because the given paths changed.
+    ReloadStart { paths: Vec<Utf8PathBuf> },
+    /// A restart is starting because the given paths changed.
+    RestartStart { paths: Vec<Utf8PathBuf> },
+    /// A module started compiling.
+    Compiling(CompilingModule),
+    /// A module finished compiling.
+    Compiled(ModuleTime),
+    /// A diagnostic was emitted during compilation.
+    Diagnostic(GhcDiagnostic),
+    /// Compilation finished.
+    Summary {
+        result: CompilationResult,
+        modules_loaded: ModulesLoaded,
+        #[serde(serialize_with = "serialize_millis")]
+        duration_ms: Duration,
+    },
+    /// A hook is starting.
+    HookStart { hook: String, command: String },
+    /// A hook finished.
+    ///
+    /// `success` is only known for synchronous shell commands.
+    HookFinish {
+        hook: String,
+        command: String,
+        success: Option<bool>,
+        #[serde(serialize_with = "serialize_millis")]
+        duration_ms: Duration,
+    },
+    /// The test hooks finished.
+    ///
+    /// `success` is only known if there are synchronous shell test hooks.
+    TestFinish {
+        success: Option<bool>,
+        #[serde(serialize_with = "serialize_millis")]
+        duration_ms: Duration,
+    },
+    /// The modules with the longest average compile times this session, emitted on shutdown.
+    SlowestModules { modules: Vec<SlowModule> },
+}
+
+pub(crate) fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/// An event with the metadata included on every line.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    timestamp: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// A serialized event, and a channel to notify once it's been written.
+type Message = (String, Option<oneshot::Sender<()>>);
+
+/// A clonable handle to the event stream, which may be disabled.
+///
+/// Events are written in order by a background task, so they can be emitted from synchronous
+/// code like line hooks; see [`EventStream::queue`].
+#[derive(Clone, Default)]
+pub struct EventStream {
+    sender: Option<mpsc::UnboundedSender<Message>>,
+}
+
+impl Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("enabled", &self.sender.is_some())
+            .finish()
+    }
+}
+
+impl EventStream {
+    /// Open an event stream at the given path.
+    ///
+    /// If the path is a Unix socket, events are sent to it; otherwise, the path is truncated and
+    /// events are written to it as a file.
+    pub async fn open(path: &Utf8Path) -> miette::Result<Self> {
+        let is_socket = tokio::fs::metadata(path)
+            .await
+            .map(|metadata| metadata.file_type().is_socket())
+            .unwrap_or(false);
+
+        let writer: Writer = if is_socket {
+            Box::new(
+                UnixStream::connect(path)
+                    .await
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to connect to event stream socket {path}"))?,
+            )
+        } else {
+            Box::new(
+                tokio::fs::File::create(path)
+                    .await
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to open event stream file {path}"))?,
+            )
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_events(writer, receiver));
+
+        Ok(Self {
+            sender: Some(sender),
+        })
+    }
+
+    /// Write an event to the stream, if it's enabled, and wait for it to be written.
+    ///
+    /// Failures are logged rather than returned so that a broken consumer can't interrupt the
+    /// `ghci` session.
+    pub async fn emit(&self, event: Event) {
+        let (sender, written) = oneshot::channel();
+        if self.send(event, Some(sender)) {
+            let _ = written.await;
+        }
+    }
+
+    /// Write an event to the stream, if it's enabled, without waiting for it to be written.
+    ///
+    /// Events are still written in the order they're emitted or queued.
+    pub fn queue(&self, event: Event) {
+        self.send(event, None);
+    }
+
+    /// Send an event to the writer task, returning `false` if it won't be written.
+    fn send(&self, event: Event, written: Option<oneshot::Sender<()>>) -> bool {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return false,
+        };
+
+        let line = match serialize(&event, SystemTime::now()) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::debug!(?event, "Failed to serialize event: {err}");
+                return false;
+            }
+        };
+
+        sender.send((line, written)).is_ok()
+    }
+}
+
+/// Write events to the stream until every [`EventStream`] handle is dropped.
+async fn write_events(mut writer: Writer, mut receiver: mpsc::UnboundedReceiver<Message>) {
+    while let Some((line, written)) = receiver.recv().await {
+        if let Err(err) = async {
+            writer.write_all(line.as_bytes()).await?;
+            writer.flush().await
+        }
+        .await
+        {
+            tracing::debug!("Failed to write to event stream: {err}");
+        }
+        if let Some(written) = written {
+            let _ = written.send(());
+        }
+    }
+}
+
+fn serialize(event: &Event, now: SystemTime) -> serde_json::Result<String> {
+    let mut line = serde_json::to_string(&Envelope {
+        version: VERSION,
+        timestamp: humantime::format_rfc3339_millis(now).to_string(),
+        event,
+    })?;
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ghci::parse::PositionRange;
+    use crate::ghci::parse::Severity;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_serialize() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(
+            serialize(&Event::Startup, now).unwrap(),
+            "{\"version\":1,\"timestamp\":\"2023-11-14T22:13:20.000Z\",\"event\":\"startup\"}\n"
+        );
+
+        assert_eq!(
+            serialize(
+                &Event::ReloadStart {
+                    paths: vec!["src/MyLib.hs".into()]
+                },
+                now
+            )
+            .unwrap(),
+            "{\"version\":1,\"timestamp\":\"2023-11-14T22:13:20.000Z\",\
+            \"event\":\"reload-start\",\"paths\":[\"src/MyLib.hs\"]}\n"
+        );
+
+        assert_eq!(
+            serialize(
+                &Event::Diagnostic(GhcDiagnostic {
+                    severity: Severity::Error,
+                    path: Some("src/MyLib.hs".into()),
+                    span: PositionRange::new(1, 2, 1, 3),
+                    message: "puppy".into(),
+                    ..Default::default()
+                }),
+                now
+            )
+            .unwrap(),
+            "{\"version\":1,\"timestamp\":\"2023-11-14T22:13:20.000Z\",\
+            \"event\":\"diagnostic\",\"severity\":\"error\",\"path\":\"src/MyLib.hs\",\
+            \"span\":{\"start\":{\"line\":1,\"column\":2},\"end\":{\"line\":1,\"column\":3}},\
+            \"message\":\"puppy\",\"code\":null,\"flags\":[],\"hints\":[]}\n"
+        );
+
+        assert_eq!(
+            serialize(
+                &Event::Summary {
+                    result: CompilationResult::Ok,
+                    modules_loaded: ModulesLoaded::Count(3),
+                    duration_ms: Duration::from_millis(1500),
+                },
+                now
+            )
+            .unwrap(),
+            "{\"version\":1,\"timestamp\":\"2023-11-14T22:13:20.000Z\",\
+            \"event\":\"summary\",\"result\":\"ok\",\"modules_loaded\":{\"count\":3},\
+            \"duration_ms\":1500}\n"
+        );
+    }
+}
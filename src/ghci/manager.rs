@@ -1,11 +1,19 @@
 //! Subsystem for [`Ghci`] to support graceful shutdown.
 
 use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use itertools::Itertools;
 use miette::miette;
 use miette::Context;
 use miette::IntoDiagnostic;
+use nix::unistd::Pid;
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use tokio::sync::Mutex;
@@ -13,7 +21,9 @@ use tracing::instrument;
 
 use crate::event_filter::FileEvent;
 use crate::ghci::CompilationLog;
+use crate::ghci::GhciCommand;
 use crate::hooks;
+use crate::hooks::HookAborted;
 use crate::hooks::LifecycleEvent;
 use crate::shutdown::ShutdownHandle;
 
@@ -28,6 +38,98 @@ pub enum InternalCommand {
     ToggleTrackWarnings,
     /// Toggle `--repl-no-load` flag in the GHCi command.
     ToggleNoLoad,
+    /// Run every eval command, not just the ones in recompiled modules.
+    EvalAll,
+}
+
+impl InternalCommand {
+    /// The kind of reload this command performs.
+    fn reload_kind(&self) -> GhciReloadKind {
+        match self {
+            InternalCommand::ToggleTrackWarnings | InternalCommand::ToggleNoLoad => {
+                GhciReloadKind::Restart
+            }
+            InternalCommand::EvalAll => GhciReloadKind::None,
+        }
+    }
+}
+
+/// A command sent to [`Ghci`] over the control socket.
+#[derive(Debug, Clone)]
+pub enum ControlCommand {
+    /// Reload all modules in the `ghci` session.
+    Reload,
+    /// Restart the `ghci` session.
+    Restart,
+    /// Run the test hooks.
+    Test,
+    /// Evaluate a command in the `ghci` session.
+    Eval(GhciCommand),
+    /// Execute an internal ghciwatch command.
+    Internal(InternalCommand),
+}
+
+impl ControlCommand {
+    /// The kind of reload this command triggers, used to decide whether to interrupt it.
+    fn reload_kind(&self) -> GhciReloadKind {
+        match self {
+            ControlCommand::Reload => GhciReloadKind::Reload,
+            ControlCommand::Restart => GhciReloadKind::Restart,
+            ControlCommand::Test | ControlCommand::Eval(_) => GhciReloadKind::None,
+            ControlCommand::Internal(command) => command.reload_kind(),
+        }
+    }
+}
+
+/// The output of a successful [`ControlCommand`].
+#[derive(Debug, Clone, Default)]
+pub struct ControlOutput {
+    /// The compilation log.
+    pub log: CompilationLog,
+    /// The output `ghci` printed, for [`ControlCommand::Eval`].
+    pub output: Option<String>,
+}
+
+/// The result of a [`ControlCommand`]; either its output or an error message.
+pub type ControlResult = Result<ControlOutput, String>;
+
+/// A handle used to respond to a [`ControlCommand`].
+///
+/// [`WatcherEvent`]s are cloned when they're dispatched (and may be dispatched again if they're
+/// interrupted), so the sender is shared and taken by the first response.
+#[derive(Debug, Clone)]
+pub struct ControlReply(Arc<std::sync::Mutex<Option<oneshot::Sender<ControlResult>>>>);
+
+impl ControlReply {
+    /// Create a new reply handle and the receiver for its response.
+    pub fn new() -> (Self, oneshot::Receiver<ControlResult>) {
+        let (sender, receiver) = oneshot::channel();
+        (
+            Self(Arc::new(std::sync::Mutex::new(Some(sender)))),
+            receiver,
+        )
+    }
+
+    /// Send the response, if one hasn't been sent already.
+    fn send(&self, result: ControlResult) {
+        let sender = self
+            .0
+            .lock()
+            .expect("Control reply lock was poisoned")
+            .take();
+        if let Some(sender) = sender {
+            // The client may have disconnected.
+            let _ = sender.send(result);
+        }
+    }
+
+    /// Has a response been sent already?
+    fn is_sent(&self) -> bool {
+        self.0
+            .lock()
+            .expect("Control reply lock was poisoned")
+            .is_none()
+    }
 }
 
 /// An event sent to [`Ghci`] by the watcher or TUI.
@@ -48,72 +150,170 @@ pub enum WatcherEvent {
         /// The internal command to execute.
         command: InternalCommand,
     },
+    /// Execute commands from the control socket and respond with their results.
+    Control {
+        /// The commands to execute in order, and where to send their results.
+        requests: Vec<(ControlCommand, ControlReply)>,
+    },
 }
 
 impl WatcherEvent {
     /// When we interrupt an event to reload, add the file events together so that we don't lose
     /// work.
-    fn merge(&mut self, other: WatcherEvent) {
-        match (&mut *self, &other) {
+    ///
+    /// Control socket requests come from separate clients, so they're queued rather than
+    /// replaced. If `other` can't be merged into this event, it's returned so that it can be
+    /// dispatched afterwards.
+    fn merge(&mut self, other: WatcherEvent) -> Option<WatcherEvent> {
+        match (&mut *self, other) {
             (
                 WatcherEvent::Reload { events },
                 WatcherEvent::Reload {
                     events: other_events,
                 },
             ) => {
-                events.extend(other_events.clone());
+                events.extend(other_events);
+                None
+            }
+            (
+                WatcherEvent::Control { requests },
+                WatcherEvent::Control {
+                    requests: other_requests,
+                },
+            ) => {
+                requests.extend(other_requests);
+                None
             }
+            // Control requests are never replaced, and never replace other events.
+            (WatcherEvent::Control { .. }, other @ WatcherEvent::Action { .. })
+            | (WatcherEvent::Control { .. }, other @ WatcherEvent::Internal { .. })
+            | (_, other @ WatcherEvent::Control { .. }) => Some(other),
             // Actions and internal commands can't be merged, just replace with the newer one
-            (_, WatcherEvent::Action { .. }) | (_, WatcherEvent::Internal { .. }) => {
+            (_, other @ WatcherEvent::Action { .. })
+            | (_, other @ WatcherEvent::Internal { .. }) => {
                 *self = other;
+                None
             }
             // If we have an action/internal and get a reload, keep the action/internal
             (WatcherEvent::Action { .. }, WatcherEvent::Reload { .. })
-            | (WatcherEvent::Internal { .. }, WatcherEvent::Reload { .. }) => {}
+            | (WatcherEvent::Internal { .. }, WatcherEvent::Reload { .. })
+            | (WatcherEvent::Control { .. }, WatcherEvent::Reload { .. }) => None,
+        }
+    }
+
+    /// Respond to any control socket requests in this event with an error.
+    fn reply_err(&self, message: &str) {
+        if let WatcherEvent::Control { requests } = self {
+            for (_command, reply) in requests {
+                reply.send(Err(message.to_owned()));
+            }
         }
     }
 }
 
+/// Add an event to the queue, merging it into the last queued event if possible.
+fn enqueue(pending: &mut VecDeque<WatcherEvent>, event: WatcherEvent) {
+    let unmerged = match pending.back_mut() {
+        Some(last) => last.merge(event),
+        None => Some(event),
+    };
+    if let Some(event) = unmerged {
+        pending.push_back(event);
+    }
+}
+
 /// Start the [`Ghci`] subsystem.
 #[instrument(skip_all, level = "debug")]
 pub async fn run_ghci(
     mut handle: ShutdownHandle,
-    opts: GhciOpts,
+    mut opts: GhciOpts,
     mut receiver: mpsc::Receiver<WatcherEvent>,
 ) -> miette::Result<()> {
     // This function is pretty tricky! We need to handle shutdowns at each stage, and the process
     // is a little different each time, so the `select!`s can't be consolidated.
 
     let no_interrupt_reloads = opts.no_interrupt_reloads;
-    let mut ghci = Ghci::new(handle.clone(), opts)
-        .await
-        .wrap_err("Failed to start `ghci`")?;
+    let interruptible = opts.interruptible.clone();
+    let mut crashes = CrashCounter::new(opts.max_crash_restarts);
+    // If we're not restarting `ghci` when it crashes, nothing is sent on this channel; instead,
+    // `ghciwatch` shuts down.
+    let (crash_sender, mut crash_receiver) = mpsc::channel(8);
+    let restart_on_crash = opts.max_crash_restarts.is_some();
+    if restart_on_crash {
+        opts.crash_sender = Some(crash_sender);
+    }
+    let mut ghci = loop {
+        match Ghci::new(handle.clone(), opts.clone()).await {
+            Err(err) if err.downcast_ref::<HookAborted>().is_some() => {
+                // Nothing has started yet, so wait for a change and try again.
+                tracing::info!("Startup aborted by a hook; waiting for changes to try again");
+                tokio::select! {
+                    _ = handle.on_shutdown_requested() => {
+                        return Ok(());
+                    }
+                    ret = receiver.recv() => {
+                        let event = ret.ok_or_else(|| miette!("ghci event channel closed"))?;
+                        tracing::debug!(?event, "Retrying startup after ghci event");
+                        event.reply_err("GHCi isn't running");
+                    }
+                }
+            }
+            result => break result.wrap_err("Failed to start `ghci`")?,
+        }
+    };
 
     // Wait for ghci to finish loading.
     let mut log = CompilationLog::default();
+    let mut crashed_on_startup = false;
     tokio::select! {
         _ = handle.on_shutdown_requested() => {
-            ghci.stop().await.wrap_err("Failed to quit ghci")?;
+            ghci.shutdown().await.wrap_err("Failed to quit ghci")?;
+        }
+        Some(_) = crash_receiver.recv() => {
+            crashed_on_startup = true;
         }
         startup_result = ghci.initialize(&mut log, [LifecycleEvent::Startup(hooks::When::After)]) => {
-            startup_result?;
+            match startup_result {
+                Err(err) if err.downcast_ref::<HookAborted>().is_some() => {
+                    tracing::debug!("Startup aborted by a hook");
+                }
+                Err(err) if restart_on_crash && is_crash(&err) => {
+                    crashed_on_startup = true;
+                }
+                result => result?,
+            }
         }
     }
 
     let ghci = Arc::new(Mutex::new(ghci));
-    // The event to respond to. If we interrupt a reload, we may begin the loop with `Some(_)` in
+    if crashed_on_startup
+        && recover_from_crash(&ghci, &mut crashes, &mut crash_receiver, &mut handle)
+            .await?
+            .is_break()
+    {
+        return Ok(());
+    }
+    // The events to respond to. If we interrupt a reload, we may begin the loop with events in
     // here.
-    let mut maybe_event = None;
+    let mut pending = VecDeque::new();
     loop {
-        let mut event = match maybe_event.take() {
+        let event = match pending.pop_front() {
             Some(event) => event,
             None => {
                 // If we don't already have an event to respond to, wait for filesystem events.
                 let event = tokio::select! {
                     _ = handle.on_shutdown_requested() => {
-                        ghci.lock().await.stop().await.wrap_err("Failed to quit ghci")?;
+                        ghci.lock().await.shutdown().await.wrap_err("Failed to quit ghci")?;
                         break;
                     }
+                    Some(pid) = crash_receiver.recv() => {
+                        if pid != ghci.lock().await.process_group_id {
+                            tracing::debug!(%pid, "Ignoring crash of a previous ghci session");
+                        } else if recover_from_crash(&ghci, &mut crashes, &mut crash_receiver, &mut handle).await?.is_break() {
+                            break;
+                        }
+                        continue;
+                    }
                     ret = receiver.recv() => {
                         ret.ok_or_else(|| miette!("ghci event channel closed"))?
                     }
@@ -123,6 +323,32 @@ pub async fn run_ghci(
             }
         };
 
+        if crashes.given_up {
+            // `ghci` crashed too many times in a row; now that something's changed, try again.
+            tracing::info!("Restarting GHCi");
+            crashes.reset();
+            let restarted = match try_restart(&mut *ghci.lock().await, &mut crash_receiver).await {
+                Err(err) if err.downcast_ref::<HookAborted>().is_some() => {
+                    tracing::error!("Not restarting GHCi until files change");
+                    crashes.given_up = true;
+                    continue;
+                }
+                result => result?,
+            };
+            if !restarted
+                && recover_from_crash(&ghci, &mut crashes, &mut crash_receiver, &mut handle)
+                    .await?
+                    .is_break()
+            {
+                break;
+            }
+            // Restarting `ghci` picks up any changed files, but other events still need to be
+            // dispatched.
+            if crashes.given_up || matches!(event, WatcherEvent::Reload { .. }) {
+                continue;
+            }
+        }
+
         // This channel notifies us what kind of reload is triggered, which we can use to inform
         // our decision to interrupt the reload or not.
         let (reload_sender, reload_receiver) = oneshot::channel();
@@ -137,24 +363,53 @@ pub async fn run_ghci(
             _ = handle.on_shutdown_requested() => {
                 // Cancel any in-progress reloads. This releases the lock so we don't block here.
                 task.abort();
-                ghci.lock().await.stop().await.wrap_err("Failed to quit ghci")?;
+                ghci.lock().await.shutdown().await.wrap_err("Failed to quit ghci")?;
                 break;
             }
+            Some(pid) = crash_receiver.recv() => {
+                // `ghci` may be stuck waiting for output which will never come, so cancel the
+                // in-progress event and dispatch it again once `ghci` is restarted.
+                task.abort();
+                pending.push_front(event);
+                if pid != ghci.lock().await.process_group_id {
+                    tracing::debug!(%pid, "Ignoring crash of a previous ghci session");
+                } else if recover_from_crash(&ghci, &mut crashes, &mut crash_receiver, &mut handle).await?.is_break() {
+                    break;
+                } else if crashes.given_up {
+                    for event in pending.drain(..) {
+                        event.reply_err("GHCi crashed");
+                    }
+                }
+            }
             Some(new_event) = receiver.recv() => {
                 tracing::debug!(?new_event, "Received ghci event from watcher while reloading");
-                if !no_interrupt_reloads && should_interrupt(reload_receiver).await {
-                    // Merge the events together so we don't lose progress.
-                    // Then, the next iteration of the loop will pick up the `maybe_event` value
-                    // and respond immediately.
-                    event.merge(new_event);
-                    maybe_event = Some(event);
-
-                    // Cancel the in-progress reload. This releases the `ghci` lock to prevent a deadlock.
+                // New file events also interrupt eval commands and tests.
+                let running = matches!(new_event, WatcherEvent::Reload { .. })
+                    && interruptible.load(Ordering::SeqCst);
+                if !no_interrupt_reloads && should_interrupt(reload_receiver, running).await {
+                    if running && !matches!(event, WatcherEvent::Reload { .. }) {
+                        // Running tests or evals for an action or command again wouldn't pick up
+                        // the changed files, so respond to the file event instead.
+                        event.reply_err("Interrupted by a file change");
+                        pending.push_front(new_event);
+                    } else {
+                        // Merge the events together so we don't lose progress.
+                        // Then, the next iteration of the loop will pick up the `pending`
+                        // events and respond immediately.
+                        pending.push_front(event);
+                        enqueue(&mut pending, new_event);
+                    }
+
+                    // Cancel the in-progress reload, eval, or test. This releases the `ghci` lock to
+                    // prevent a deadlock.
                     task.abort();
 
-                    // Send a SIGINT to interrupt the reload.
+                    // Send a SIGINT to interrupt the reload, eval, or test.
                     // NB: This may take a couple seconds to register.
                     ghci.lock().await.send_sigint().await?;
+                } else if matches!(new_event, WatcherEvent::Control { .. }) {
+                    // Don't drop control requests; respond once the current event finishes.
+                    enqueue(&mut pending, new_event);
                 }
             }
             ret = &mut task => {
@@ -165,29 +420,17 @@ pub async fn run_ghci(
                 match dispatch_result {
                     Ok(_) => {
                         tracing::debug!("Finished dispatching ghci event");
+                        crashes.reset();
+                        ghci.lock().await.restart_if_over_memory_limit().await?;
                     }
                     Err(e) => {
-                        // Check if this is a broken pipe error, which indicates GHCi crashed
-                        let is_broken_pipe = e
-                            .chain()
-                            .any(|e| {
-                                e.to_string().contains("broken pipe") ||
-                                e.downcast_ref::<std::io::Error>()
-                                    .map(|io_err| io_err.kind() == std::io::ErrorKind::BrokenPipe)
-                                    .unwrap_or(false)
-                            });
-
-                        if is_broken_pipe {
+                        if e.downcast_ref::<HookAborted>().is_some() {
+                            // The hook has already logged the failure; wait for the next event.
+                            tracing::debug!("Dispatching ghci event aborted by a hook");
+                        } else if is_crash(&e) {
                             tracing::error!("GHCi process crashed (broken pipe). Attempting to restart...");
-                            // Attempt to restart GHCi
-                            match ghci.lock().await.restart().await {
-                                Ok(_) => {
-                                    tracing::info!("Successfully restarted GHCi after crash");
-                                    // Continue the loop to handle any pending events
-                                }
-                                Err(restart_err) => {
-                                    return Err(restart_err).wrap_err("Failed to restart GHCi after crash");
-                                }
+                            if recover_from_crash(&ghci, &mut crashes, &mut crash_receiver, &mut handle).await?.is_break() {
+                                break;
                             }
                         } else {
                             // For other errors, propagate them
@@ -244,26 +487,229 @@ async fn dispatch(
         WatcherEvent::Internal { command } => {
             tracing::info!(?command, "Executing internal command");
             let mut ghci = ghci.lock().await;
+            let kind = command.reload_kind();
+
+            run_internal(&mut ghci, command, &mut CompilationLog::default()).await?;
 
-            match command {
-                InternalCommand::ToggleTrackWarnings => {
-                    ghci.toggle_track_warnings().await?;
+            // Notify that we're done (the toggles trigger a restart)
+            let _ = reload_sender.send(kind);
+        }
+        WatcherEvent::Control { requests } => {
+            let mut ghci = ghci.lock().await;
+            let mut reload_sender = Some(reload_sender);
+            let mut first_error = None;
+            // Requests which were answered before this event was interrupted aren't run again.
+            for (command, reply) in requests.into_iter().filter(|(_, reply)| !reply.is_sent()) {
+                tracing::info!(?command, "Executing control socket command");
+                if let Some(reload_sender) = reload_sender.take() {
+                    let _ = reload_sender.send(command.reload_kind());
                 }
-                InternalCommand::ToggleNoLoad => {
-                    ghci.toggle_no_load().await?;
+                let result = dispatch_control(&mut ghci, command).await;
+                reply.send(match &result {
+                    Ok(output) => Ok(output.clone()),
+                    Err(err) => Err(err.chain().map(ToString::to_string).join(": ")),
+                });
+                if let Err(err) = result {
+                    first_error.get_or_insert(err);
                 }
             }
+            if let Some(err) = first_error {
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn dispatch_control(
+    ghci: &mut Ghci,
+    command: ControlCommand,
+) -> miette::Result<ControlOutput> {
+    let mut log = CompilationLog::default();
+    let mut output = None;
+
+    match command {
+        ControlCommand::Reload => {
+            ghci.reload_all(&mut log).await?;
+        }
+        ControlCommand::Restart => {
+            log = ghci.restart().await?;
+        }
+        ControlCommand::Test => {
+            ghci.test(&mut log).await?;
+        }
+        ControlCommand::Eval(command) => {
+            output = Some(ghci.run_command(&command, &mut log).await?);
+        }
+        ControlCommand::Internal(command) => {
+            run_internal(ghci, command, &mut log).await?;
+        }
+    }
+
+    Ok(ControlOutput { log, output })
+}
 
-            // Notify that we're done (triggers a restart)
-            let _ = reload_sender.send(GhciReloadKind::Restart);
+async fn run_internal(
+    ghci: &mut Ghci,
+    command: InternalCommand,
+    log: &mut CompilationLog,
+) -> miette::Result<()> {
+    match command {
+        InternalCommand::ToggleTrackWarnings => {
+            ghci.toggle_track_warnings().await?;
+        }
+        InternalCommand::ToggleNoLoad => {
+            ghci.toggle_no_load().await?;
+        }
+        InternalCommand::EvalAll => {
+            ghci.eval_all(log).await?;
         }
     }
     Ok(())
 }
 
+/// Does this error indicate that `ghci` crashed?
+fn is_crash(err: &miette::Report) -> bool {
+    // Check if this is a broken pipe error, which indicates GHCi crashed
+    err.chain().any(|e| {
+        e.to_string().contains("broken pipe")
+            || e.downcast_ref::<std::io::Error>()
+                .map(|io_err| io_err.kind() == std::io::ErrorKind::BrokenPipe)
+                .unwrap_or(false)
+    })
+}
+
+/// Tracks how many times `ghci` has crashed in a row, so that we don't restart it in a tight loop.
+struct CrashCounter {
+    /// How many times `ghci` has crashed since it last handled an event.
+    count: u32,
+    /// How many times to restart `ghci` in a row before giving up.
+    max: u32,
+    /// How long to wait before restarting `ghci`, if `--max-crash-restarts` is given.
+    backoff: Option<ExponentialBackoff>,
+    /// If we gave up on restarting `ghci`, it's restarted on the next event instead.
+    given_up: bool,
+}
+
+impl CrashCounter {
+    fn new(max_crash_restarts: Option<u32>) -> Self {
+        Self {
+            // Without `--max-crash-restarts`, `ghciwatch` shuts down when `ghci` exits, but we can
+            // still try restarting once (right away) if we notice a crash when writing to `ghci`.
+            max: max_crash_restarts.unwrap_or(1),
+            count: 0,
+            backoff: max_crash_restarts.map(|_| ExponentialBackoff {
+                max_interval: Duration::from_secs(30),
+                max_elapsed_time: None,
+                ..Default::default()
+            }),
+            given_up: false,
+        }
+    }
+
+    /// Forget about previous crashes.
+    fn reset(&mut self) {
+        self.count = 0;
+        if let Some(backoff) = &mut self.backoff {
+            backoff.reset();
+        }
+        self.given_up = false;
+    }
+}
+
+/// Restart `ghci` after it crashes.
+///
+/// If `ghci` keeps crashing, we wait longer between each restart, and stop restarting it after
+/// `--max-crash-restarts` crashes in a row.
+///
+/// Breaks if a shutdown was requested while waiting to restart `ghci`.
+#[instrument(level = "debug", skip_all)]
+async fn recover_from_crash(
+    ghci: &Mutex<Ghci>,
+    crashes: &mut CrashCounter,
+    crash_receiver: &mut mpsc::Receiver<Pid>,
+    handle: &mut ShutdownHandle,
+) -> miette::Result<ControlFlow<()>> {
+    let mut ghci = ghci.lock().await;
+    loop {
+        crashes.count += 1;
+        let stderr = ghci.stderr_tail().await;
+        tracing::error!(
+            "GHCi crashed {} {}; last stderr:\n{stderr}",
+            crashes.count,
+            if crashes.count == 1 { "time" } else { "times" }
+        );
+
+        if crashes.count > crashes.max {
+            tracing::error!("Not restarting GHCi until files change");
+            crashes.given_up = true;
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        if let Some(backoff) = &mut crashes.backoff {
+            let delay = backoff.next_backoff().unwrap_or(backoff.max_interval);
+            tracing::info!("Restarting GHCi in {delay:.2?}");
+            tokio::select! {
+                _ = handle.on_shutdown_requested() => {
+                    ghci.shutdown().await.wrap_err("Failed to quit ghci")?;
+                    return Ok(ControlFlow::Break(()));
+                }
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+
+        match try_restart(&mut ghci, crash_receiver).await {
+            Ok(true) => return Ok(ControlFlow::Continue(())),
+            Ok(false) => {}
+            Err(err) if err.downcast_ref::<HookAborted>().is_some() => {
+                tracing::error!("Not restarting GHCi until files change");
+                crashes.given_up = true;
+                return Ok(ControlFlow::Continue(()));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Restart `ghci` after it crashes, returning `false` if it crashes again while starting up.
+async fn try_restart(
+    ghci: &mut Ghci,
+    crash_receiver: &mut mpsc::Receiver<Pid>,
+) -> miette::Result<bool> {
+    let previous_pid = ghci.process_group_id;
+    tokio::select! {
+        result = ghci.restart_after_crash() => match result {
+            Ok(_) => {
+                tracing::info!("Successfully restarted GHCi after crash");
+                Ok(true)
+            }
+            Err(err) if is_crash(&err) => Ok(false),
+            Err(err) => Err(err).wrap_err("Failed to restart GHCi after crash"),
+        },
+        _ = next_crash(crash_receiver, previous_pid) => Ok(false),
+    }
+}
+
+/// Wait for a `ghci` session other than `previous_pid` to crash.
+async fn next_crash(crash_receiver: &mut mpsc::Receiver<Pid>, previous_pid: Pid) {
+    while let Some(pid) = crash_receiver.recv().await {
+        if pid != previous_pid {
+            return;
+        }
+    }
+    // The sender was dropped, so no more crashes will be reported.
+    std::future::pending().await
+}
+
 /// Should we interrupt a reload with a new event?
+///
+/// If `running` is set, eval commands or tests are running and are interrupted regardless of the
+/// kind of reload, like when they're running after a restart.
 #[instrument(level = "debug", skip_all)]
-async fn should_interrupt(reload_receiver: oneshot::Receiver<GhciReloadKind>) -> bool {
+async fn should_interrupt(
+    reload_receiver: oneshot::Receiver<GhciReloadKind>,
+    running: bool,
+) -> bool {
     let reload_kind = match reload_receiver.await {
         Ok(kind) => kind,
         Err(err) => {
@@ -272,6 +718,11 @@ async fn should_interrupt(reload_receiver: oneshot::Receiver<GhciReloadKind>) ->
         }
     };
 
+    if running {
+        tracing::debug!(?reload_kind, "Interrupting eval commands or tests");
+        return true;
+    }
+
     match reload_kind {
         GhciReloadKind::None | GhciReloadKind::Restart => {
             // Nothing to do, wait for the task to finish.
@@ -284,3 +735,72 @@ async fn should_interrupt(reload_receiver: oneshot::Receiver<GhciReloadKind>) ->
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn control(command: ControlCommand) -> (WatcherEvent, oneshot::Receiver<ControlResult>) {
+        let (reply, receiver) = ControlReply::new();
+        (
+            WatcherEvent::Control {
+                requests: vec![(command, reply)],
+            },
+            receiver,
+        )
+    }
+
+    #[test]
+    fn test_merge_control_events() {
+        let (mut event, mut first) = control(ControlCommand::Reload);
+        let (other, mut second) = control(ControlCommand::Test);
+
+        assert!(event.merge(other).is_none());
+        match &event {
+            WatcherEvent::Control { requests } => assert_eq!(requests.len(), 2),
+            _ => panic!("Expected a control event, got {event:?}"),
+        }
+
+        event.reply_err("Interrupted");
+        assert_eq!(first.try_recv().unwrap().unwrap_err(), "Interrupted");
+        assert_eq!(second.try_recv().unwrap().unwrap_err(), "Interrupted");
+    }
+
+    #[test]
+    fn test_enqueue_control_events() {
+        let (control_event, mut receiver) = control(ControlCommand::Test);
+        let mut pending = VecDeque::from([WatcherEvent::Action {
+            command: "true".to_owned(),
+        }]);
+
+        // Control requests don't replace other events, and aren't replaced by them.
+        enqueue(&mut pending, control_event);
+        enqueue(
+            &mut pending,
+            WatcherEvent::Internal {
+                command: InternalCommand::ToggleNoLoad,
+            },
+        );
+        assert_eq!(pending.len(), 3);
+        assert!(matches!(pending[1], WatcherEvent::Control { .. }));
+
+        for event in pending {
+            event.reply_err("Done");
+        }
+        assert_eq!(receiver.try_recv().unwrap().unwrap_err(), "Done");
+    }
+
+    #[test]
+    fn test_crash_counter_backoff() {
+        // Without `--max-crash-restarts`, `ghci` is restarted once without waiting.
+        let crashes = CrashCounter::new(None);
+        assert_eq!(crashes.max, 1);
+        assert!(crashes.backoff.is_none());
+
+        let crashes = CrashCounter::new(Some(3));
+        assert_eq!(crashes.max, 3);
+        assert!(crashes.backoff.is_some());
+    }
+}
@@ -0,0 +1,102 @@
+//! Measuring the memory used by a `ghci` session.
+
+use nix::unistd::Pid;
+
+use crate::byte_size::ByteSize;
+
+/// Get the total resident memory of the processes in the given process group.
+///
+/// Returns `None` if the memory can't be measured, like if `/proc` isn't available (on macOS,
+/// for example).
+pub fn process_group_rss(process_group_id: Pid) -> Option<ByteSize> {
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::debug!("Failed to read `/proc`: {err}");
+            return None;
+        }
+    };
+
+    let mut total = 0;
+    for entry in entries.flatten() {
+        let pid = entry.file_name();
+        let pid = match pid.to_str() {
+            Some(pid) if pid.bytes().all(|byte| byte.is_ascii_digit()) => pid,
+            _ => continue,
+        };
+        // Processes may exit while we're looking at them, so errors here are ignored.
+        let in_group = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|stat| parse_process_group(&stat))
+            == Some(process_group_id.as_raw());
+        if !in_group {
+            continue;
+        }
+        if let Some(rss) = std::fs::read_to_string(format!("/proc/{pid}/status"))
+            .ok()
+            .and_then(|status| parse_vm_rss(&status))
+        {
+            total += rss;
+        }
+    }
+
+    Some(ByteSize(total))
+}
+
+/// Parse the process group ID from the contents of `/proc/PID/stat`.
+fn parse_process_group(stat: &str) -> Option<i32> {
+    // The second field is the command name in parentheses, which may contain spaces and
+    // parentheses of its own, so we skip past the last closing parenthesis. After that, the fields
+    // are the state, the parent PID, and then the process group ID.
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(2)?.parse().ok()
+}
+
+/// Parse the resident memory in bytes from the contents of `/proc/PID/status`.
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_process_group() {
+        assert_eq!(
+            parse_process_group("1234 (ghc-9.4.8) S 1200 1201 1200 0 -1 4194304 12345 0"),
+            Some(1201)
+        );
+        assert_eq!(
+            parse_process_group("1234 (weird) name) R 1 4321 4321 0 -1 4194304 12345 0"),
+            Some(4321)
+        );
+        assert_eq!(parse_process_group("1234 (truncated"), None);
+    }
+
+    #[test]
+    fn test_parse_vm_rss() {
+        assert_eq!(
+            parse_vm_rss(indoc!(
+                "
+                Name:	ghc-9.4.8
+                VmPeak:	 2097152 kB
+                VmRSS:	  524288 kB
+                Threads:	8
+                "
+            )),
+            Some(512 << 20)
+        );
+        assert_eq!(parse_vm_rss("Name:\tkthreadd\nThreads:\t1\n"), None);
+    }
+}
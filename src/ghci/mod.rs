@@ -1,8 +1,6 @@
 //! The core [`Ghci`] session struct.
 
 use command_group::AsyncCommandGroup;
-use nix::sys::signal;
-use nix::sys::signal::Signal;
 use owo_colors::OwoColorize;
 use owo_colors::Stream::Stdout;
 use std::borrow::Borrow;
@@ -11,14 +9,17 @@ use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::fmt::Debug;
+use std::io::IsTerminal;
 use std::path::Path;
-use std::process::ExitStatus;
 use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 use std::time::Instant;
 use tokio::fs::OpenOptions;
 use tokio::io::DuplexStream;
 use tokio::sync::oneshot;
-use tokio::task::JoinHandle;
 
 use aho_corasick::AhoCorasick;
 use camino::Utf8Path;
@@ -28,7 +29,6 @@ use miette::IntoDiagnostic;
 use miette::WrapErr;
 use nix::unistd::Pid;
 use tokio::io::AsyncBufReadExt;
-use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::sync::mpsc;
 use tracing::instrument;
@@ -41,20 +41,37 @@ use stdout::GhciStdout;
 
 mod stderr;
 use stderr::GhciStderr;
+use stderr::StderrEvent;
 
 mod process;
 use process::GhciProcess;
 
+mod memory;
+
+pub mod progress;
+use progress::ProgressHandle;
+
+pub mod compile_times;
+use compile_times::CompileTimes;
+use compile_times::SLOWEST_MODULES_COUNT;
+
 pub mod manager;
 
 mod error_log;
+pub use error_log::ErrorFileFormat;
 use error_log::ErrorLog;
 
+mod event_stream;
+pub use event_stream::Event;
+pub use event_stream::EventStream;
+
 pub mod parse;
 use parse::parse_eval_commands;
+use parse::parse_imports;
 use parse::CompilationResult;
 use parse::EvalCommand;
-use parse::ModulesLoaded;
+use parse::GhcDiagnostic;
+use parse::Severity;
 use parse::ShowPaths;
 
 mod ghci_command;
@@ -78,18 +95,30 @@ mod warning_formatter;
 mod warning_tracker;
 use warning_tracker::WarningTracker;
 
+mod recompilation;
+use recompilation::RecompiledModule;
+
 use crate::aho_corasick::AhoCorasickExt;
 use crate::buffers::LINE_BUFFER_CAPACITY;
+use crate::byte_size::ByteSize;
 use crate::cli::Opts;
 use crate::clonable_command::ClonableCommand;
 use crate::event_filter::FileEvent;
 use crate::format_bulleted_list;
 use crate::haskell_source_file::is_haskell_source_file;
+use crate::hook_windows::HookWindows;
+use crate::hook_windows::WindowPaths;
 use crate::hooks;
+use crate::hooks::HookAborted;
+use crate::hooks::HookContext;
 use crate::hooks::HookOpts;
 use crate::hooks::LifecycleEvent;
+use crate::hooks::OnChangeHook;
 use crate::ignore::GlobMatcher;
 use crate::incremental_reader::IncrementalReader;
+use crate::lsp::DiagnosticsUpdate;
+use crate::lsp::LspTarget;
+use crate::maybe_async_command::CommandHandles;
 use crate::normal_path::NormalPath;
 use crate::shutdown::ShutdownHandle;
 use crate::CommandExt;
@@ -99,6 +128,9 @@ use crate::StringCase;
 /// private-use-area codepoints or something in the future.
 pub const PROMPT: &str = "###~GHCIWATCH-PROMPT~###";
 
+/// How many lines of `ghci`'s `stderr` to show when it crashes.
+const CRASH_STDERR_LINES: usize = 20;
+
 /// Options for constructing a [`Ghci`]. This is like a lower-effort builder interface, mostly provided
 /// because Rust tragically lacks named arguments.
 ///
@@ -111,16 +143,37 @@ pub struct GhciOpts {
     pub command: ClonableCommand,
     /// A path to write `ghci` errors to.
     pub error_path: Option<Utf8PathBuf>,
+    /// The format to write `ghci` errors in.
+    pub error_file_format: ErrorFileFormat,
     /// Enable running eval commands in files.
     pub enable_eval: bool,
+    /// If given, write eval results back into source files. Filesystem events during these
+    /// windows are ignored, so the writes don't trigger reloads.
+    pub write_eval_results: Option<HookWindows>,
+    /// Evaluate Haddock `-- >>>` examples as eval commands.
+    pub eval_doctests: bool,
+    /// Interrupt eval commands which run for longer than this.
+    pub eval_timeout: Option<Duration>,
     /// Lifecycle hooks, mostly `ghci` commands to run at certain points.
     pub hooks: HookOpts,
     /// Restart the `ghci` session when paths matching these globs are changed.
     pub restart_globs: GlobMatcher,
     /// Reload the `ghci` session when paths matching these globs are changed.
     pub reload_globs: GlobMatcher,
+    /// Shell commands to run before reloading when paths matching their globs are changed.
+    pub on_change: Vec<(GlobMatcher, OnChangeHook)>,
     /// Determines whether we should interrupt a reload in progress or not.
     pub no_interrupt_reloads: bool,
+    /// Set while eval commands or tests are running, so that new events can interrupt them.
+    pub interruptible: Arc<AtomicBool>,
+    /// How many times to restart `ghci` in a row if it crashes. If `None`, `ghciwatch` shuts
+    /// down when `ghci` crashes.
+    pub max_crash_restarts: Option<u32>,
+    /// Restart the `ghci` session when it uses more than this much memory.
+    pub max_memory: Option<ByteSize>,
+    /// Where to send the process group ID of a `ghci` session which crashed, if it should be
+    /// restarted.
+    pub crash_sender: Option<mpsc::Sender<Pid>>,
     /// Where to write what `ghci` emits to `stdout`. Inherits parent's `stdout` by default.
     pub stdout_writer: GhciWriter,
     /// Where to write what `ghci` emits to `stderr`. Inherits parent's `stderr` by default.
@@ -129,6 +182,16 @@ pub struct GhciOpts {
     pub clear: bool,
     /// Whether to track warnings across recompilations.
     pub track_warnings: bool,
+    /// Whether to explain why modules were recompiled after reloads.
+    pub explain_recompilation: bool,
+    /// Where to send diagnostics after compilation, if serving them over LSP.
+    pub lsp_sender: Option<mpsc::Sender<DiagnosticsUpdate>>,
+    /// Where to write lifecycle events.
+    pub event_stream: EventStream,
+    /// If given, report progress while compiling modules.
+    pub progress: Option<ProgressHandle>,
+    /// Compile times for each module this session.
+    pub compile_times: CompileTimes,
 }
 
 impl GhciOpts {
@@ -172,41 +235,80 @@ impl GhciOpts {
             stderr_writer = tui_writer.clone();
             tui_reader = Some(tui_reader_inner);
         } else {
-            stdout_writer = if let Some(ref output_path) = opts.output_file {
-                let file = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(output_path)
-                    .await
-                    .into_diagnostic()
-                    .wrap_err_with(|| format!("Failed to open output file: {}", output_path))?;
-                GhciWriter::tee(GhciWriter::stdout(), file)
-            } else {
-                GhciWriter::stdout()
-            };
+            stdout_writer = Self::console_writer(opts).await?;
             stderr_writer = GhciWriter::stderr();
             tui_reader = None;
         }
 
+        let event_stream = match &opts.event_stream {
+            Some(path) => EventStream::open(path).await?,
+            None => EventStream::default(),
+        };
+
         Ok((
             Self {
                 command,
                 error_path: opts.error_file.clone(),
+                error_file_format: opts.error_file_format,
                 enable_eval: opts.enable_eval,
+                write_eval_results: opts.write_eval_results.then(HookWindows::default),
+                eval_doctests: opts.eval_doctests,
+                eval_timeout: opts.eval_timeout,
                 hooks: opts.hooks.clone(),
                 restart_globs: opts.watch.restart_globs()?,
                 reload_globs: opts.watch.reload_globs()?,
+                on_change: opts.watch.on_change_hooks()?,
                 no_interrupt_reloads: opts.no_interrupt_reloads,
+                interruptible: Default::default(),
+                max_crash_restarts: opts.max_crash_restarts,
+                max_memory: opts.max_ghci_memory,
+                crash_sender: None,
                 stdout_writer,
                 stderr_writer,
                 clear: opts.clear,
                 track_warnings: opts.track_warnings,
+                explain_recompilation: opts.explain_recompilation,
+                lsp_sender: None,
+                event_stream: event_stream.clone(),
+                progress: opts.progress.then(|| {
+                    // Only show a status line if `ghci`'s output is going straight to a terminal.
+                    let status_line = !opts.tui
+                        && opts.output_file.is_none()
+                        && opts.lsp != Some(LspTarget::Stdio)
+                        && std::io::stdout().is_terminal();
+                    ProgressHandle::new(status_line)
+                }),
+                compile_times: CompileTimes::new(event_stream),
             },
             tui_reader,
         ))
     }
 
+    /// Construct the writer for `ghci`'s `stdout` when the TUI isn't in use, duplicating the
+    /// output to the `--output` file if one is given.
+    async fn console_writer(opts: &Opts) -> miette::Result<GhciWriter> {
+        let console = if opts.lsp == Some(LspTarget::Stdio) {
+            // `stdout` is reserved for LSP messages.
+            GhciWriter::stderr()
+        } else {
+            GhciWriter::stdout()
+        };
+        Ok(match &opts.output_file {
+            Some(output_path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(output_path)
+                    .await
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to open output file: {}", output_path))?;
+                GhciWriter::tee(console, file)
+            }
+            None => console,
+        })
+    }
+
     #[instrument(skip_all, level = "trace")]
     fn clear(&self) {
         if self.clear {
@@ -250,12 +352,26 @@ pub struct Ghci {
     targets: ModuleSet,
     /// Eval commands, if `opts.enable_eval` is set.
     eval_commands: BTreeMap<NormalPath, Vec<EvalCommand>>,
+    /// The contents `eval_commands` were parsed from, if `opts.write_eval_results` is set.
+    eval_sources: BTreeMap<NormalPath, String>,
+    /// Diagnostics from the last time each module's eval commands were run.
+    ///
+    /// Eval commands only run in recompiled modules, so these are published along with each
+    /// compilation's diagnostics to keep mismatches in other modules from disappearing.
+    eval_diagnostics: BTreeMap<NormalPath, Vec<GhcDiagnostic>>,
     /// Search paths / current working directory for this `ghci` session.
     search_paths: ShowPaths,
     /// Tasks running `async:` shell commands in the background.
-    command_handles: Vec<JoinHandle<miette::Result<ExitStatus>>>,
+    command_handles: CommandHandles,
     /// Warning tracker for managing warnings across recompilations.
     warning_tracker: WarningTracker,
+    /// Paths which changed, triggering the current reload or restart. Exported to shell hooks.
+    changed_paths: Vec<Utf8PathBuf>,
+    /// The result of the last compilation, used to run first-error hooks.
+    previous_result: Option<CompilationResult>,
+    /// Are eval commands running? If they're cancelled and `ghci` is interrupted, the after-eval
+    /// hooks still need to run.
+    evaluating: bool,
 }
 
 impl Debug for Ghci {
@@ -271,20 +387,48 @@ impl Ghci {
     ///
     /// This starts a number of asynchronous tasks to manage the `ghci` session's input and output
     /// streams.
-    #[instrument(skip_all, level = "debug", name = "ghci")]
-    pub async fn new(mut shutdown: ShutdownHandle, opts: GhciOpts) -> miette::Result<Self> {
-        let mut command_handles = Vec::new();
-        {
-            let span = tracing::debug_span!("before_startup_shell");
-            let _enter = span.enter();
-            opts.hooks
-                .run_shell_hooks(
-                    LifecycleEvent::Startup(hooks::When::Before),
-                    &mut command_handles,
-                )
-                .await?;
-        }
+    pub async fn new(shutdown: ShutdownHandle, opts: GhciOpts) -> miette::Result<Self> {
+        let mut command_handles = CommandHandles::default();
+        Self::before_startup(&opts, &[], &mut command_handles).await?;
+        Self::start(shutdown, opts, Vec::new(), command_handles).await
+    }
+
+    /// Run the before-startup hooks for a new `ghci` session because the given paths changed.
+    ///
+    /// This is separate from [`Ghci::start`] so that if a hook aborts a restart, the previous
+    /// session is left running.
+    #[instrument(skip_all, level = "debug")]
+    async fn before_startup(
+        opts: &GhciOpts,
+        changed_paths: &[Utf8PathBuf],
+        command_handles: &mut CommandHandles,
+    ) -> miette::Result<()> {
+        opts.event_stream.emit(Event::Startup).await;
+        opts.hooks
+            .run_shell_hooks(
+                LifecycleEvent::Startup(hooks::When::Before),
+                command_handles,
+                &opts.event_stream,
+                &HookContext {
+                    changed_paths: changed_paths.to_vec(),
+                    error_file: opts.error_path.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
 
+    /// Start a new `ghci` session because the given paths changed.
+    ///
+    /// `command_handles` are the background commands started by the previous session, if any,
+    /// so that restartable commands can be stopped before they're started again.
+    #[instrument(skip_all, level = "debug", name = "ghci")]
+    async fn start(
+        mut shutdown: ShutdownHandle,
+        opts: GhciOpts,
+        changed_paths: Vec<Utf8PathBuf>,
+        command_handles: CommandHandles,
+    ) -> miette::Result<Self> {
         let mut group = {
             let mut command = opts.command.as_tokio();
 
@@ -325,14 +469,28 @@ impl Ghci {
         // TODO: Is this a good capacity? Maybe it should just be 1.
         let (stderr_sender, stderr_receiver) = mpsc::channel(8);
 
+        let compile_times = opts.compile_times.clone();
+        let progress = opts.progress.clone();
+        let reader = IncrementalReader::new(stdout)
+            .with_writer(opts.stdout_writer.clone())
+            .with_line_hook(move |line| {
+                compile_times.line(line);
+                progress.as_ref().and_then(|progress| progress.line(line))
+            });
         let stdout = GhciStdout {
-            reader: IncrementalReader::new(stdout).with_writer(opts.stdout_writer.clone()),
+            reader,
             stderr_sender: stderr_sender.clone(),
             buffer: vec![0; LINE_BUFFER_CAPACITY],
             prompt_patterns: AhoCorasick::from_anchored_patterns([PROMPT]),
+            progress: opts.progress.clone(),
+            compile_times: opts.compile_times.clone(),
         };
 
-        let stdin = GhciStdin { stdin };
+        let stdin = GhciStdin {
+            stdin,
+            process_group_id,
+            eval_module: None,
+        };
 
         shutdown
             .spawn("stderr", |shutdown| {
@@ -342,6 +500,7 @@ impl Ghci {
                     writer: opts.stderr_writer.clone(),
                     receiver: stderr_receiver,
                     buffer: String::with_capacity(LINE_BUFFER_CAPACITY),
+                    progress: opts.progress.clone(),
                 }
                 .run()
             })
@@ -355,12 +514,15 @@ impl Ghci {
                     shutdown,
                     restart_receiver,
                     process_group_id,
+                    hooks: opts.hooks.clone(),
+                    event_stream: opts.event_stream.clone(),
+                    crash_sender: opts.crash_sender.clone(),
                 }
                 .run(group)
             })
             .await;
 
-        let error_log = ErrorLog::new(opts.error_path.clone());
+        let error_log = ErrorLog::new(opts.error_path.clone(), opts.error_file_format);
 
         Ok(Ghci {
             opts,
@@ -372,12 +534,17 @@ impl Ghci {
             error_log,
             targets: Default::default(),
             eval_commands: Default::default(),
+            eval_sources: Default::default(),
+            eval_diagnostics: Default::default(),
             search_paths: ShowPaths {
                 cwd: crate::current_dir_utf8()?,
                 search_paths: Default::default(),
             },
             command_handles,
             warning_tracker: WarningTracker::new(),
+            changed_paths,
+            previous_result: None,
+            evaluating: false,
         })
     }
 
@@ -427,10 +594,17 @@ impl Ghci {
         let mut needs_reload = Vec::new();
         let mut needs_add = Vec::new();
         let mut needs_remove = Vec::new();
+        let mut on_change = BTreeMap::<usize, Vec<NormalPath>>::new();
         for event in events {
             let path = event.as_path();
             let path = self.relative_path(path)?;
 
+            for (index, (matcher, _)) in self.opts.on_change.iter().enumerate() {
+                if matcher.matched(&path).is_whitelist() {
+                    on_change.entry(index).or_default().push(path.clone());
+                }
+            }
+
             let restart_match = self.opts.restart_globs.matched(&path);
             let reload_match = self.opts.reload_globs.matched(&path);
             let path_is_haskell_source_file = is_haskell_source_file(&path);
@@ -493,6 +667,7 @@ impl Ghci {
             needs_reload,
             needs_add,
             needs_remove,
+            on_change,
         })
     }
 
@@ -511,6 +686,8 @@ impl Ghci {
         let start_instant = Instant::now();
         let actions = self.get_reload_actions(events.clone()).await?;
         let _ = kind_sender.send(actions.kind());
+        self.changed_paths = paths_for_event(actions.iter());
+        self.run_on_change_hooks(&actions).await?;
 
         // Track which files were directly changed in this reload
         self.warning_tracker.reset_changed_files();
@@ -520,6 +697,12 @@ impl Ghci {
         }
 
         if actions.needs_restart() {
+            self.opts
+                .event_stream
+                .emit(Event::RestartStart {
+                    paths: self.changed_paths.clone(),
+                })
+                .await;
             self.opts.clear();
             tracing::info!(
                 "Restarting ghci:\n{}",
@@ -534,6 +717,12 @@ impl Ghci {
         let mut log = CompilationLog::default();
 
         if actions.needs_modify() {
+            self.opts
+                .event_stream
+                .emit(Event::ReloadStart {
+                    paths: self.changed_paths.clone(),
+                })
+                .await;
             self.opts.clear();
             self.run_hooks(LifecycleEvent::Reload(hooks::When::Before), &mut log)
                 .await?;
@@ -566,6 +755,7 @@ impl Ghci {
         }
 
         if actions.needs_modify() {
+            self.explain_recompilation(&log).await;
             self.finish_compilation(
                 start_instant,
                 &mut log,
@@ -574,20 +764,78 @@ impl Ghci {
             .await?;
         }
 
+        self.changed_paths.clear();
+        self.prune_command_handles();
+
+        Ok(())
+    }
+
+    /// Reload all the modules in this `ghci` session, regardless of which files have changed.
+    #[instrument(skip_all, level = "debug")]
+    pub async fn reload_all(&mut self, log: &mut CompilationLog) -> miette::Result<()> {
+        let start_instant = Instant::now();
+        self.warning_tracker.reset_changed_files();
+        self.changed_paths.clear();
+
+        self.opts
+            .event_stream
+            .emit(Event::ReloadStart { paths: Vec::new() })
+            .await;
+        self.opts.clear();
+        self.run_hooks(LifecycleEvent::Reload(hooks::When::Before), log)
+            .await?;
+        tracing::info!("Reloading ghci");
+        self.stdin.reload(&mut self.stdout, log).await?;
+        self.refresh_eval_commands().await?;
+        self.finish_compilation(
+            start_instant,
+            log,
+            [LifecycleEvent::Reload(hooks::When::After)],
+        )
+        .await?;
+
         self.prune_command_handles();
 
         Ok(())
     }
 
-    /// Restart the `ghci` session.
+    /// Restart the `ghci` session, returning the compilation log from startup.
     #[instrument(skip_all, level = "debug")]
-    async fn restart(&mut self) -> miette::Result<()> {
+    async fn restart(&mut self) -> miette::Result<CompilationLog> {
         let mut log = CompilationLog::default();
 
         self.run_hooks(LifecycleEvent::Restart(hooks::When::Before), &mut log)
             .await?;
+        self.respawn(log).await
+    }
+
+    /// Restart the `ghci` session after it crashed, returning the compilation log from startup.
+    ///
+    /// The before-restart hooks aren't run, because there's no `ghci` session to run them in.
+    #[instrument(skip_all, level = "debug")]
+    async fn restart_after_crash(&mut self) -> miette::Result<CompilationLog> {
+        self.respawn(CompilationLog::default()).await
+    }
+
+    /// Stop the `ghci` session and start a new one in its place.
+    ///
+    /// The before-startup hooks run first, so if one of them aborts, the current session (if
+    /// it's still alive) keeps running.
+    async fn respawn(&mut self, mut log: CompilationLog) -> miette::Result<CompilationLog> {
+        Self::before_startup(&self.opts, &self.changed_paths, &mut self.command_handles).await?;
         self.stop().await?;
-        let new = Self::new(self.shutdown.clone(), self.opts.clone()).await?;
+        let changed_paths = std::mem::take(&mut self.changed_paths);
+        let command_handles = std::mem::take(&mut self.command_handles);
+        let mut new = Self::start(
+            self.shutdown.clone(),
+            self.opts.clone(),
+            changed_paths,
+            command_handles,
+        )
+        .await?;
+        // Keep the error log so that its sequence numbers keep increasing across restarts.
+        std::mem::swap(&mut new.error_log, &mut self.error_log);
+        new.previous_result = self.previous_result;
         let _ = std::mem::replace(self, new);
         self.initialize(
             &mut log,
@@ -597,45 +845,190 @@ impl Ghci {
             ],
         )
         .await?;
+        self.changed_paths.clear();
 
-        Ok(())
+        Ok(log)
     }
 
     /// Run the user provided test command.
     #[instrument(skip_all, level = "debug")]
     async fn test(&mut self, log: &mut CompilationLog) -> miette::Result<()> {
-        self.run_hooks(LifecycleEvent::Test, log).await?;
+        if self
+            .opts
+            .hooks
+            .select(LifecycleEvent::Test)
+            .next()
+            .is_none()
+        {
+            return Ok(());
+        }
+
+        let start_instant = Instant::now();
+        let _interruptible = InterruptibleGuard::new(&self.opts.interruptible);
+        let success = self.run_hooks(LifecycleEvent::Test, log).await?;
+        self.opts
+            .event_stream
+            .emit(Event::TestFinish {
+                success,
+                duration_ms: start_instant.elapsed(),
+            })
+            .await;
         Ok(())
     }
 
-    /// Run the eval commands, if enabled.
+    /// Run a command in the `ghci` session, like an expression or a `:`-command, and return its
+    /// output.
+    #[instrument(skip(self, log), level = "debug")]
+    async fn run_command(
+        &mut self,
+        command: &GhciCommand,
+        log: &mut CompilationLog,
+    ) -> miette::Result<String> {
+        let _interruptible = InterruptibleGuard::new(&self.opts.interruptible);
+        let timeout = self.opts.eval_timeout;
+        let output = self
+            .stdin
+            .run_command_with_timeout(&mut self.stdout, command, timeout, log)
+            .await?;
+        if let (None, Some(timeout)) = (&output, timeout) {
+            return Err(miette!(
+                "Command timed out after {}",
+                humantime::format_duration(timeout)
+            ));
+        }
+        Ok(output.unwrap_or_default())
+    }
+
+    /// Run every eval command, not just the ones in recompiled modules.
+    ///
+    /// Output mismatches are added to the `log` but aren't written to the error log until the
+    /// next compilation, so that the diagnostics from the last compilation are kept.
+    #[instrument(skip_all, level = "debug")]
+    pub async fn eval_all(&mut self, log: &mut CompilationLog) -> miette::Result<()> {
+        if !self.opts.enable_eval {
+            tracing::warn!("Eval commands are disabled; use `--enable-eval` to enable them");
+            return Ok(());
+        }
+        if self.previous_result == Some(CompilationResult::Err) {
+            tracing::warn!("Compilation failed, not running eval commands");
+            return Ok(());
+        }
+        self.eval(None, log).await
+    }
+
+    /// The modules which were changed or recompiled in the given compilation.
+    fn recompiled_paths(&self, log: &CompilationLog) -> BTreeSet<NormalPath> {
+        let mut paths = self.warning_tracker.changed_files().clone();
+        for module in &log.compiled_modules {
+            match self.relative_path(&module.path) {
+                Ok(path) => {
+                    paths.insert(path);
+                }
+                Err(err) => {
+                    tracing::debug!(path = %module.path, "Failed to normalize path: {err}");
+                }
+            }
+        }
+        paths
+    }
+
+    /// Run the eval commands in the given modules (or all modules, if `None`), if enabled.
     #[instrument(skip_all, level = "debug")]
-    async fn eval(&mut self, log: &mut CompilationLog) -> miette::Result<()> {
+    async fn eval(
+        &mut self,
+        paths: Option<&BTreeSet<NormalPath>>,
+        log: &mut CompilationLog,
+    ) -> miette::Result<()> {
         if !self.opts.enable_eval {
             return Ok(());
         }
 
-        // TODO: This `clone` is ugly but I can't get the borrow checker to accept it otherwise.
-        // Might be more efficient to swap it out for a default, but then it gets trickier to
-        // restore the old value when the function returns.
-        for (path, commands) in self.eval_commands.clone() {
+        let eval_commands = self
+            .eval_commands
+            .iter()
+            .filter(|(path, commands)| {
+                !commands.is_empty() && paths.is_none_or(|paths| paths.contains(*path))
+            })
+            .map(|(path, commands)| (path.clone(), commands.clone()))
+            .collect::<Vec<_>>();
+        if eval_commands.is_empty() {
+            return Ok(());
+        }
+
+        let _interruptible = InterruptibleGuard::new(&self.opts.interruptible);
+        self.run_hooks(LifecycleEvent::Eval(hooks::When::Before), log)
+            .await?;
+
+        self.evaluating = true;
+        let result = self.eval_modules(eval_commands, log).await;
+        self.evaluating = false;
+        result?;
+
+        self.run_hooks(LifecycleEvent::Eval(hooks::When::After), log)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Run the given eval commands, for [`Ghci::eval`].
+    async fn eval_modules(
+        &mut self,
+        eval_commands: Vec<(NormalPath, Vec<EvalCommand>)>,
+        log: &mut CompilationLog,
+    ) -> miette::Result<()> {
+        for (path, commands) in eval_commands {
+            // If the `module` was already compiled, `ghci` may have loaded the interface file instead
+            // of the interpreted bytecode, giving us this error message when we attempt to
+            // load the top-level scope with `:module + *{module}`:
+            //
+            //     module 'Mercury.Typescript.Golden' is not interpreted
+            //
+            // We use `:add *{module}` to force interpreting the module. We do this here instead of in
+            // `add_module` to save time if eval commands aren't used (or aren't needed for a
+            // particular module).
+            tracing::info!("Loading {path} in interpreted mode for eval commands");
+            self.interpret_module(&path, log).await?;
+            let module = self.search_paths.path_to_module(&path)?;
+
+            let mut results = Vec::new();
+            let mut diagnostics = Vec::new();
             for command in commands {
-                // If the `module` was already compiled, `ghci` may have loaded the interface file instead
-                // of the interpreted bytecode, giving us this error message when we attempt to
-                // load the top-level scope with `:module + *{module}`:
-                //
-                //     module 'Mercury.Typescript.Golden' is not interpreted
-                //
-                // We use `:add *{module}` to force interpreting the module. We do this here instead of in
-                // `add_module` to save time if eval commands aren't used (or aren't needed for a
-                // particular module).
-                tracing::info!("Loading {path} in interpreted mode for eval commands");
-                self.interpret_module(&path, log).await?;
-                let module = self.search_paths.path_to_module(&path)?;
                 tracing::info!("Eval {path}:{command}");
-                self.stdin
-                    .eval(&mut self.stdout, &module, &command.command, log)
+                let output = self
+                    .stdin
+                    .eval(
+                        &mut self.stdout,
+                        &module,
+                        &command.command,
+                        self.opts.eval_timeout,
+                        log,
+                    )
                     .await?;
+                let output = match output {
+                    Some(output) => output,
+                    None => {
+                        if let Some(timeout) = self.opts.eval_timeout {
+                            diagnostics.push(command.timed_out(path.relative(), timeout));
+                        }
+                        continue;
+                    }
+                };
+                if self.opts.write_eval_results.is_some() {
+                    // The expected output is about to be overwritten, so don't check it.
+                    results.push((command, output));
+                } else if let Some(diagnostic) = command.check_output(path.relative(), &output) {
+                    tracing::error!("{diagnostic}");
+                    diagnostics.push(diagnostic);
+                }
+            }
+            log.diagnostics.extend(diagnostics.iter().cloned());
+            if diagnostics.is_empty() {
+                self.eval_diagnostics.remove(&path);
+            } else {
+                self.eval_diagnostics.insert(path.clone(), diagnostics);
+            }
+            if !results.is_empty() {
+                self.write_eval_results(&path, &results).await?;
             }
         }
 
@@ -806,18 +1199,64 @@ impl Ghci {
         }
 
         let mut eval_commands = BTreeMap::new();
+        let mut eval_sources = BTreeMap::new();
 
         for target in self.targets.iter() {
-            let commands = Self::parse_eval_commands(target.path()).await?;
+            let (contents, commands) =
+                Self::parse_eval_commands(target.path(), self.opts.eval_doctests).await?;
             if !commands.is_empty() {
                 eval_commands.insert(target.path().clone(), commands);
+                if self.opts.write_eval_results.is_some() {
+                    eval_sources.insert(target.path().clone(), contents);
+                }
             }
         }
 
         self.eval_commands = eval_commands;
+        self.eval_sources = eval_sources;
         Ok(())
     }
 
+    /// Log a tree from each directly changed file to the modules recompiled because of it, if
+    /// `--explain-recompilation` is enabled.
+    #[instrument(skip_all, level = "debug")]
+    async fn explain_recompilation(&self, log: &CompilationLog) {
+        if !self.opts.explain_recompilation {
+            return;
+        }
+
+        let mut modules = Vec::with_capacity(log.compiled_modules.len());
+        for module in &log.compiled_modules {
+            let path = match self.relative_path(&module.path) {
+                Ok(path) => path,
+                Err(err) => {
+                    tracing::debug!(path = %module.path, "Failed to normalize path: {err}");
+                    continue;
+                }
+            };
+            // Modules may be deleted or renamed while we're looking at them, so we don't fail if
+            // they can't be read.
+            let imports = match tokio::fs::read_to_string(path.absolute()).await {
+                Ok(contents) => parse_imports(&contents),
+                Err(err) => {
+                    tracing::debug!(%path, "Failed to read module: {err}");
+                    Vec::new()
+                }
+            };
+            modules.push(RecompiledModule {
+                name: module.name.clone(),
+                path,
+                imports,
+            });
+        }
+
+        if let Some(explanation) =
+            recompilation::explain_recompilation(self.warning_tracker.changed_files(), &modules)
+        {
+            tracing::info!("{explanation}");
+        }
+    }
+
     /// Refresh `eval_commands` by reading and parsing the given files.
     #[instrument(skip_all, level = "debug")]
     async fn refresh_eval_commands_for_paths(
@@ -830,8 +1269,12 @@ impl Ghci {
 
         for path in paths {
             let path = path.borrow();
-            let commands = Self::parse_eval_commands(path).await?;
+            let (contents, commands) =
+                Self::parse_eval_commands(path, self.opts.eval_doctests).await?;
             self.eval_commands.insert(path.clone(), commands);
+            if self.opts.write_eval_results.is_some() {
+                self.eval_sources.insert(path.clone(), contents);
+            }
         }
 
         Ok(())
@@ -849,19 +1292,76 @@ impl Ghci {
 
         for path in paths {
             self.eval_commands.remove(path.borrow());
+            self.eval_sources.remove(path.borrow());
+            self.eval_diagnostics.remove(path.borrow());
         }
     }
 
-    /// Read and parse eval commands from the given `path`.
+    /// Read and parse eval commands from the given `path`, returning the file's contents and the
+    /// commands.
     #[instrument(level = "trace")]
-    async fn parse_eval_commands(path: &Utf8Path) -> miette::Result<Vec<EvalCommand>> {
+    async fn parse_eval_commands(
+        path: &Utf8Path,
+        doctests: bool,
+    ) -> miette::Result<(String, Vec<EvalCommand>)> {
         let contents = tokio::fs::read_to_string(path)
             .await
             .into_diagnostic()
             .wrap_err_with(|| format!("Failed to read {path}"))?;
-        let commands = parse_eval_commands(&contents)
+        let commands = parse_eval_commands(&contents, doctests)
             .wrap_err_with(|| format!("Failed to parse eval commands from file {path}"))?;
-        Ok(commands)
+        Ok((contents, commands))
+    }
+
+    /// Write eval results back into the file at `path`, if it hasn't changed since its eval
+    /// commands were read.
+    #[instrument(skip(self, results), level = "debug")]
+    async fn write_eval_results(
+        &mut self,
+        path: &NormalPath,
+        results: &[(EvalCommand, String)],
+    ) -> miette::Result<()> {
+        let windows = match &self.opts.write_eval_results {
+            Some(windows) => windows.clone(),
+            None => return Ok(()),
+        };
+        let source = match self.eval_sources.get(path) {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let contents = tokio::fs::read_to_string(path.absolute())
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to read {path}"))?;
+        if contents != *source {
+            tracing::warn!(
+                "{path} changed since its eval commands were read, not writing eval results"
+            );
+            return Ok(());
+        }
+
+        let new_contents = parse::write_eval_results(&contents, results);
+        if new_contents == contents {
+            return Ok(());
+        }
+
+        tracing::info!("Writing eval results to {path}");
+        {
+            // Ignore the filesystem events for our own write so it doesn't trigger a reload. Other
+            // changes made in the meantime are handled as usual.
+            let _window = windows.start(WindowPaths::Files(vec![
+                path.absolute().into(),
+                temporary_path(path.absolute())?.into(),
+            ]));
+            write_atomically(path.absolute(), &new_contents).await?;
+        }
+
+        // The byte offsets of the commands have changed, so parse them again.
+        let commands = parse_eval_commands(&new_contents, self.opts.eval_doctests)
+            .wrap_err_with(|| format!("Failed to parse eval commands from file {path}"))?;
+        self.eval_commands.insert(path.clone(), commands);
+        self.eval_sources.insert(path.clone(), new_contents);
+        Ok(())
     }
 
     /// `:add` a module or modules to the GHCi session.
@@ -1000,6 +1500,79 @@ impl Ghci {
         Ok(())
     }
 
+    /// Run the shutdown hooks, then stop this `ghci` session.
+    #[instrument(skip_all, level = "debug")]
+    async fn shutdown(&mut self) -> miette::Result<()> {
+        self.opts
+            .hooks
+            .run_shell_hooks(
+                LifecycleEvent::Shutdown,
+                &mut self.command_handles,
+                &self.opts.event_stream,
+                &HookContext {
+                    error_file: self.error_log.path().map(ToOwned::to_owned),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        self.command_handles.stop_restartable().await;
+        self.report_slowest_modules().await;
+        self.stop().await
+    }
+
+    /// Log the modules with the longest compile times this session and write them to the event
+    /// stream.
+    async fn report_slowest_modules(&self) {
+        let compile_times = &self.opts.compile_times;
+        if let Some(report) = compile_times.report(SLOWEST_MODULES_COUNT) {
+            tracing::info!("{report}");
+            self.opts
+                .event_stream
+                .emit(Event::SlowestModules {
+                    modules: compile_times.slowest(SLOWEST_MODULES_COUNT),
+                })
+                .await;
+        }
+    }
+
+    /// Restart the `ghci` session if it's using more memory than `--max-ghci-memory`.
+    #[instrument(skip_all, level = "debug")]
+    async fn restart_if_over_memory_limit(&mut self) -> miette::Result<()> {
+        let limit = match self.opts.max_memory {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let rss = match memory::process_group_rss(self.process_group_id) {
+            Some(rss) => rss,
+            None => return Ok(()),
+        };
+        tracing::debug!(%rss, %limit, "Measured ghci memory usage");
+        if rss > limit {
+            tracing::info!(
+                "Restarting GHCi because it's using {rss} of memory, more than the {limit} allowed by `--max-ghci-memory`"
+            );
+            self.restart().await?;
+        }
+        Ok(())
+    }
+
+    /// Get the last lines `ghci` wrote to `stderr`, to report when it crashes.
+    async fn stderr_tail(&self) -> String {
+        let (sender, receiver) = oneshot::channel();
+        if self
+            .stdout
+            .stderr_sender
+            .send(StderrEvent::GetBuffer { sender })
+            .await
+            .is_err()
+        {
+            return String::new();
+        }
+        let buffer = receiver.await.unwrap_or_default();
+        let lines = buffer.lines().collect::<Vec<_>>();
+        lines[lines.len().saturating_sub(CRASH_STDERR_LINES)..].join("\n")
+    }
+
     /// Stop this `ghci` session and cancel the async tasks associated with it.
     #[instrument(skip_all, level = "debug")]
     async fn stop(&mut self) -> miette::Result<()> {
@@ -1084,20 +1657,26 @@ impl Ghci {
         self.search_paths.make_relative(path)
     }
 
-    #[instrument(skip_all, level = "debug")]
+    /// Interrupt `ghci`, like when a reload, eval, or test is cancelled.
+    ///
+    /// If eval commands were cancelled, the after-eval hooks are run.
     async fn send_sigint(&mut self) -> miette::Result<()> {
-        let start_instant = Instant::now();
-        signal::killpg(self.process_group_id, Signal::SIGINT)
-            .into_diagnostic()
-            .wrap_err("Failed to send `Ctrl-C` (`SIGINT`) to ghci session")?;
-        self.stdout
-            .prompt(
-                crate::incremental_reader::FindAt::Anywhere,
-                // Ignore compilation messages.
-                &mut Default::default(),
-            )
-            .await?;
-        tracing::debug!("Interrupted ghci in {:.2?}", start_instant.elapsed());
+        self.stdin.send_sigint(&mut self.stdout).await?;
+        if std::mem::take(&mut self.evaluating) {
+            let result = self
+                .run_hooks(
+                    LifecycleEvent::Eval(hooks::When::After),
+                    &mut CompilationLog::default(),
+                )
+                .await;
+            match result {
+                // There's nothing left to abort.
+                Err(err) if err.downcast_ref::<HookAborted>().is_some() => {}
+                result => {
+                    result?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -1125,7 +1704,7 @@ impl Ghci {
     // Get rid of any handles for background commands that have finished.
     #[instrument(skip_all, level = "trace")]
     fn prune_command_handles(&mut self) {
-        self.command_handles.retain(|handle| !handle.is_finished());
+        self.command_handles.prune();
     }
 
     /// Finish a compilation process.
@@ -1145,16 +1724,39 @@ impl Ghci {
         }
 
         // Allow hooks to consume the error log by updating it before running the hooks.
-        self.write_error_log(log).await?;
+        self.publish_diagnostics(log).await?;
+
+        self.emit_compilation_events(compilation_start, log).await;
 
         for event in events {
             self.run_hooks(event, log).await?;
+            if let Some(event) = event.with_outcome(log.result()) {
+                self.run_hooks(event, log).await?;
+            }
+        }
+
+        match log.result() {
+            Some(CompilationResult::Err) => {
+                if self.previous_result != Some(CompilationResult::Err) {
+                    self.run_hooks(LifecycleEvent::FirstError, log).await?;
+                }
+                self.previous_result = Some(CompilationResult::Err);
+            }
+            Some(CompilationResult::Ok) => {
+                self.previous_result = Some(CompilationResult::Ok);
+            }
+            None => {}
         }
 
         let event = events[N - 1];
 
         if let Some(CompilationResult::Err) = log.result() {
             tracing::error!("Compilation failed");
+            for diagnostic in &log.diagnostics {
+                if let (Severity::Error, Some(code)) = (diagnostic.severity, diagnostic.code) {
+                    tracing::error!("{}: {code}: {}", diagnostic.location(), code.url());
+                }
+            }
             tracing::error!(
                 "{} failed in {:.2?}",
                 event.event_noun().first_char_to_ascii_uppercase(),
@@ -1174,9 +1776,20 @@ impl Ghci {
             };
 
             if warning_count > 0 {
+                let by_flag = self
+                    .warning_tracker
+                    .warning_count_by_flag()
+                    .into_iter()
+                    .map(|(flag, count)| format!("{count} {flag}"))
+                    .collect::<Vec<_>>();
+                let by_flag = if by_flag.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", by_flag.join(", "))
+                };
                 tracing::info!("Compilation succeeded");
                 tracing::info!(
-                    "{} Finished {} in {:.2?} ({} warning{} tracked)",
+                    "{} Finished {} in {:.2?} ({} warning{} tracked{by_flag})",
                     "Compilation succeeded".if_supports_color(Stdout, |text| text.yellow()),
                     event.event_noun(),
                     compilation_start.elapsed(),
@@ -1193,8 +1806,20 @@ impl Ghci {
                 );
             }
 
-            // Run the eval commands, if any.
-            self.eval(log).await?;
+            // Run the eval commands in the changed and recompiled modules, if any.
+            let diagnostics_before_eval = log.diagnostics.len();
+            let paths = self.recompiled_paths(log);
+            self.eval(Some(&paths), log).await?;
+            if log.diagnostics.len() > diagnostics_before_eval {
+                // Eval output mismatches are reported as diagnostics.
+                self.publish_diagnostics(log).await?;
+                for diagnostic in &log.diagnostics[diagnostics_before_eval..] {
+                    self.opts
+                        .event_stream
+                        .emit(Event::Diagnostic(diagnostic.clone()))
+                        .await;
+                }
+            }
             // Run the user-provided test command, if any.
             self.test(log).await?;
         }
@@ -1202,33 +1827,175 @@ impl Ghci {
         Ok(())
     }
 
+    /// Write the error log and send diagnostics to the LSP server, if any.
+    ///
+    /// Diagnostics from eval commands which weren't run for this compilation are included.
+    async fn publish_diagnostics(&mut self, log: &CompilationLog) -> miette::Result<()> {
+        let log = &*with_eval_diagnostics(&self.eval_diagnostics, log);
+        self.write_error_log(log).await?;
+
+        if let Some(sender) = &self.opts.lsp_sender {
+            let update = DiagnosticsUpdate {
+                cwd: self.search_paths.cwd.clone(),
+                log: log.clone(),
+            };
+            if sender.send(update).await.is_err() {
+                tracing::debug!("LSP server has stopped, not publishing diagnostics");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the diagnostics and summary from a compilation to the event stream.
+    ///
+    /// Compiled modules are written as they're compiled; see [`CompileTimes`].
+    async fn emit_compilation_events(&self, compilation_start: Instant, log: &CompilationLog) {
+        let events = &self.opts.event_stream;
+        for diagnostic in &log.diagnostics {
+            events.emit(Event::Diagnostic(diagnostic.clone())).await;
+        }
+        if let Some(summary) = log.summary {
+            events
+                .emit(Event::Summary {
+                    result: summary.result,
+                    modules_loaded: summary.modules_loaded,
+                    duration_ms: compilation_start.elapsed(),
+                })
+                .await;
+        }
+    }
+
+    /// Run the hooks for the given event.
+    ///
+    /// Returns whether the hooks succeeded, if that's known (i.e. if any synchronous shell hooks
+    /// were run).
     #[instrument(skip_all, fields(%event), level = "trace")]
     async fn run_hooks(
         &mut self,
         event: LifecycleEvent,
         log: &mut CompilationLog,
-    ) -> miette::Result<()> {
+    ) -> miette::Result<Option<bool>> {
+        let mut success = None;
+        let env = self.hook_context(log).env(event);
+
         for hook in self.opts.hooks.select(event) {
             tracing::info!(command = %hook.command, "Running {hook} command");
-            match &hook.command {
+            self.opts
+                .event_stream
+                .emit(Event::HookStart {
+                    hook: hook.name(),
+                    command: hook.command.to_string(),
+                })
+                .await;
+            let start_time = Instant::now();
+            let hook_success = match &hook.command {
                 hooks::Command::Ghci(command) => {
-                    let start_time = Instant::now();
-                    self.stdin
-                        .run_command(&mut self.stdout, command, log)
+                    let output = self
+                        .stdin
+                        .run_command_with_timeout(
+                            &mut self.stdout,
+                            command,
+                            hook.settings.timeout,
+                            log,
+                        )
                         .await?;
+                    // GHCi hooks are only considered to have failed if they time out.
+                    let hook_success = output.is_none().then_some(false);
                     if let LifecycleEvent::Test = &hook.event {
                         tracing::info!("Finished running tests in {:.2?}", start_time.elapsed());
                     }
+                    hook_success
                 }
                 hooks::Command::Shell(command) => {
-                    command.run_on(&mut self.command_handles).await?;
+                    let _window = self.opts.hooks.hook_window();
+                    hook.settings
+                        .apply(command)
+                        .envs(env.iter().cloned())
+                        .run_on(&mut self.command_handles)
+                        .await?
+                        .map(|status| status.success())
                 }
+            };
+            self.opts
+                .event_stream
+                .emit(Event::HookFinish {
+                    hook: hook.name(),
+                    command: hook.command.to_string(),
+                    success: hook_success,
+                    duration_ms: start_time.elapsed(),
+                })
+                .await;
+            if let Some(hook_success) = hook_success {
+                success = Some(success.unwrap_or(true) && hook_success);
             }
+            hook.check_success(hook_success)?;
         }
 
+        Ok(success)
+    }
+
+    /// Run the `--on-change` hooks for the paths which changed.
+    #[instrument(skip_all, level = "debug")]
+    async fn run_on_change_hooks(&mut self, actions: &ReloadActions) -> miette::Result<()> {
+        for (index, paths) in &actions.on_change {
+            let (_, hook) = &self.opts.on_change[*index];
+            tracing::info!(command = %hook.command, glob = %hook.glob, "Running on-change command");
+            self.opts
+                .event_stream
+                .emit(Event::HookStart {
+                    hook: OnChangeHook::NAME.to_owned(),
+                    command: hook.command.to_string(),
+                })
+                .await;
+            let start_time = Instant::now();
+            let context = HookContext {
+                changed_paths: paths_for_event(paths),
+                error_file: self.error_log.path().map(ToOwned::to_owned),
+                ..Default::default()
+            };
+            let window = self.opts.hooks.hook_window();
+            let success = hook
+                .settings
+                .apply(&hook.command)
+                .envs(context.env(OnChangeHook::NAME))
+                .run_on(&mut self.command_handles)
+                .await?
+                .map(|status| status.success());
+            drop(window);
+            self.opts
+                .event_stream
+                .emit(Event::HookFinish {
+                    hook: OnChangeHook::NAME.to_owned(),
+                    command: hook.command.to_string(),
+                    success,
+                    duration_ms: start_time.elapsed(),
+                })
+                .await;
+            hook.settings
+                .check_success(OnChangeHook::NAME, &hook.command, success)?;
+        }
         Ok(())
     }
 
+    /// Get the context to export to shell hooks.
+    fn hook_context(&self, log: &CompilationLog) -> HookContext {
+        let count = |severity| {
+            log.diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == severity)
+                .count()
+        };
+
+        HookContext {
+            changed_paths: self.changed_paths.clone(),
+            result: log.result(),
+            error_count: count(Severity::Error),
+            warning_count: count(Severity::Warning),
+            error_file: self.error_log.path().map(ToOwned::to_owned),
+        }
+    }
+
     /// Display tracked warnings excluding files that were compiled in the current cycle.
     #[instrument(skip_all, level = "trace")]
     async fn display_tracked_warnings_excluding_compiled(&self, log: &CompilationLog) {
@@ -1275,53 +2042,22 @@ impl Ghci {
         &mut self,
         log: &CompilationLog,
     ) -> miette::Result<()> {
-        use crate::ghci::parse::Severity;
-        use std::collections::HashSet;
-
-        let path = match self.error_log.path() {
-            Some(path) => path,
-            None => {
-                tracing::debug!("No error log path, not writing");
-                return Ok(());
-            }
-        };
-
-        let file = tokio::fs::File::create(path).await.into_diagnostic()?;
-        let mut writer = tokio::io::BufWriter::new(file);
-
-        // Write compilation summary header if compilation succeeded
-        if let Some(summary) = log.summary {
-            if let CompilationResult::Ok = summary.result {
-                tracing::debug!(%path, "Writing 'All good'");
-                let modules_loaded = if summary.modules_loaded != ModulesLoaded::Count(1) {
-                    format!("{} modules", summary.modules_loaded)
-                } else {
-                    format!("{} module", summary.modules_loaded)
-                };
-                writer
-                    .write_all(format!("All good ({modules_loaded})\n").as_bytes())
-                    .await
-                    .into_diagnostic()?;
-            }
-        }
-
-        // Write current compilation diagnostics
-        for diagnostic in &log.diagnostics {
-            tracing::debug!(%diagnostic, "Writing current compilation diagnostic");
-            writer
-                .write_all(diagnostic.to_string().as_bytes())
-                .await
-                .into_diagnostic()?;
+        if self.error_log.path().is_none() {
+            tracing::debug!("No error log path, not writing");
+            return Ok(());
         }
 
         // Create a set of diagnostics from current compilation to avoid duplicates
         // We'll use a simple string-based deduplication approach
-        let mut current_diagnostics: HashSet<String> = HashSet::new();
-        for diagnostic in &log.diagnostics {
-            current_diagnostics.insert(diagnostic.to_string());
-        }
+        let current_diagnostics: HashSet<String> = log
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
 
-        // Write tracked warnings (only warnings, not errors) that are not already in current compilation
+        // Add tracked warnings (only warnings, not errors) that are not already in current
+        // compilation after the current compilation diagnostics.
+        let mut merged = log.clone();
         for file_warnings in self.warning_tracker.get_all_warnings().values() {
             for warning in file_warnings {
                 // Only include warnings, not errors
@@ -1329,28 +2065,28 @@ impl Ghci {
                     continue;
                 }
 
-                let warning_str = warning.to_string();
-
                 // Skip if this warning is already in the current compilation log
-                if current_diagnostics.contains(&warning_str) {
+                if current_diagnostics.contains(&warning.to_string()) {
                     continue;
                 }
 
                 tracing::debug!(%warning, "Writing tracked warning");
-                writer
-                    .write_all(warning_str.as_bytes())
-                    .await
-                    .into_diagnostic()?;
+                merged.diagnostics.push(warning.clone());
             }
         }
 
-        // Flush and shutdown the writer
-        writer.shutdown().await.into_diagnostic()?;
-
-        Ok(())
+        self.error_log.write(&merged).await
     }
 }
 
+/// Get the relative forms of the given paths, for the event stream.
+fn paths_for_event<'a>(paths: impl IntoIterator<Item = &'a NormalPath>) -> Vec<Utf8PathBuf> {
+    paths
+        .into_iter()
+        .map(|path| path.relative().to_owned())
+        .collect()
+}
+
 /// Actions needed to perform a reload.
 ///
 /// See [`Ghci::reload`].
@@ -1364,9 +2100,20 @@ struct ReloadActions {
     needs_add: Vec<NormalPath>,
     /// Paths to modules which need an `:unadd`.
     needs_remove: Vec<NormalPath>,
+    /// Changed paths matching each `--on-change` hook, by the hook's index.
+    on_change: BTreeMap<usize, Vec<NormalPath>>,
 }
 
 impl ReloadActions {
+    /// Iterate over all the paths in these actions.
+    fn iter(&self) -> impl Iterator<Item = &NormalPath> {
+        self.needs_restart
+            .iter()
+            .chain(&self.needs_remove)
+            .chain(&self.needs_add)
+            .chain(&self.needs_reload)
+    }
+
     /// Do any modules need to be added, removed, or reloaded?
     fn needs_modify(&self) -> bool {
         !self.needs_add.is_empty() || !self.needs_reload.is_empty() || !self.needs_remove.is_empty()
@@ -1400,6 +2147,74 @@ pub enum GhciReloadKind {
     Restart,
 }
 
+/// Marks eval commands or tests as running (and therefore interruptible by new events) until it's
+/// dropped.
+struct InterruptibleGuard(Arc<AtomicBool>);
+
+impl InterruptibleGuard {
+    fn new(interruptible: &Arc<AtomicBool>) -> Self {
+        interruptible.store(true, Ordering::SeqCst);
+        Self(interruptible.clone())
+    }
+}
+
+impl Drop for InterruptibleGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Write `contents` to `path` atomically, by writing to a temporary file in the same directory and
+/// renaming it over the original.
+async fn write_atomically(path: &Utf8Path, contents: &str) -> miette::Result<()> {
+    let temporary = temporary_path(path)?;
+    tokio::fs::write(&temporary, contents)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to write {temporary}"))?;
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        tokio::fs::set_permissions(&temporary, metadata.permissions())
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to set permissions of {temporary}"))?;
+    }
+    tokio::fs::rename(&temporary, path)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to move {temporary} to {path}"))
+}
+
+/// Add the diagnostics from the last run of each module's eval commands to the `log`, unless
+/// compilation failed (in which case eval commands can't run).
+fn with_eval_diagnostics<'a>(
+    eval_diagnostics: &BTreeMap<NormalPath, Vec<GhcDiagnostic>>,
+    log: &'a CompilationLog,
+) -> Cow<'a, CompilationLog> {
+    if log.result() == Some(CompilationResult::Err) {
+        return Cow::Borrowed(log);
+    }
+    let missing = eval_diagnostics
+        .values()
+        .flatten()
+        .filter(|diagnostic| !log.diagnostics.contains(diagnostic))
+        .cloned()
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Cow::Borrowed(log);
+    }
+    let mut log = log.clone();
+    log.diagnostics.extend(missing);
+    Cow::Owned(log)
+}
+
+/// The temporary file [`write_atomically`] writes to before moving it into place.
+fn temporary_path(path: &Utf8Path) -> miette::Result<Utf8PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| miette!("Path has no file name: {path}"))?;
+    Ok(path.with_file_name(format!(".{file_name}.ghciwatch-tmp")))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1416,6 +2231,7 @@ mod tests {
             path: Some(Utf8PathBuf::from(path)),
             span: PositionRange::new(1, 1, 1, 1),
             message: message.to_string(),
+            ..Default::default()
         }
     }
 
@@ -1440,6 +2256,7 @@ mod tests {
             }),
             diagnostics,
             compiled_modules: modules,
+            module_times: vec![],
         }
     }
 
@@ -1633,6 +2450,7 @@ mod tests {
                 path: Some("src/A.hs".into()),
                 span: PositionRange::new(1, 1, 1, 1),
                 message: "Warning in A".to_string(),
+                ..Default::default()
             }],
         );
         warnings.insert(
@@ -1642,6 +2460,7 @@ mod tests {
                 path: Some("src/B.hs".into()),
                 span: PositionRange::new(2, 1, 2, 1),
                 message: "Warning in B".to_string(),
+                ..Default::default()
             }],
         );
 
@@ -1658,12 +2477,14 @@ mod tests {
                     path: Some("src/A.hs".into()),
                     span: PositionRange::new(1, 1, 1, 1),
                     message: "Warning in A".to_string(),
+                    ..Default::default()
                 },
             ],
             summary: Some(CompilationSummary {
                 result: CompilationResult::Ok,
                 modules_loaded: ModulesLoaded::Count(1),
             }),
+            module_times: vec![],
         };
 
         // Simulate filtering: when we display tracked warnings, we should exclude file A
@@ -1712,6 +2533,7 @@ mod tests {
                 path: Some("src/A.hs".into()),
                 span: PositionRange::new(1, 1, 1, 1),
                 message: "Unused import".to_string(),
+                ..Default::default()
             }],
         );
 
@@ -1723,6 +2545,7 @@ mod tests {
                 path: Some("src/B.hs".into()),
                 span: PositionRange::new(2, 1, 2, 1),
                 message: "Type error".to_string(),
+                ..Default::default()
             }],
         );
 
@@ -1737,11 +2560,13 @@ mod tests {
                 path: Some("src/C.hs".into()),
                 span: PositionRange::new(3, 1, 3, 1),
                 message: "Current warning".to_string(),
+                ..Default::default()
             }],
             summary: Some(CompilationSummary {
                 result: CompilationResult::Ok,
                 modules_loaded: ModulesLoaded::Count(1),
             }),
+            module_times: vec![],
         };
 
         // Test the logic for combining diagnostics
@@ -1832,6 +2657,7 @@ mod tests {
                 path: Some("src/A.hs".into()),
                 span: PositionRange::new(10, 5, 10, 15),
                 message: "Unused import warning".to_string(),
+                ..Default::default()
             }],
         );
 
@@ -1842,6 +2668,7 @@ mod tests {
                 path: Some("src/B.hs".into()),
                 span: PositionRange::new(20, 1, 20, 10),
                 message: "Unused variable warning".to_string(),
+                ..Default::default()
             }],
         );
 
@@ -1857,18 +2684,21 @@ mod tests {
                     path: Some("src/C.hs".into()),
                     span: PositionRange::new(30, 1, 30, 5),
                     message: "Current compilation warning".to_string(),
+                    ..Default::default()
                 },
                 GhcDiagnostic {
                     severity: Severity::Error,
                     path: Some("src/C.hs".into()),
                     span: PositionRange::new(31, 1, 31, 5),
                     message: "Current compilation error".to_string(),
+                    ..Default::default()
                 },
             ],
             compiled_modules: vec![CompilingModule {
                 name: "C".to_string(),
                 path: "src/C.hs".into(),
             }],
+            module_times: vec![],
         };
 
         // Simulate the write_error_log_with_tracked_warnings method logic
@@ -1969,12 +2799,14 @@ mod tests {
                     path: Some("src/A.hs".into()),
                     span: PositionRange::new(10, 5, 10, 15),
                     message: "Unused import warning".to_string(),
+                    ..Default::default()
                 },
             ],
             compiled_modules: vec![CompilingModule {
                 name: "A".to_string(),
                 path: "src/A.hs".into(),
             }],
+            module_times: vec![],
         };
 
         // Clear the file and test again
@@ -2051,4 +2883,61 @@ mod tests {
         // Clean up
         let _ = fs::remove_file(&error_log_path);
     }
+
+    #[test]
+    fn test_with_eval_diagnostics() {
+        let base_dir = Utf8PathBuf::from("/tmp/test");
+        let mismatch_a = make_diagnostic(Severity::Error, "src/A.hs", "Eval mismatch in A");
+        let mismatch_b = make_diagnostic(Severity::Error, "src/B.hs", "Eval mismatch in B");
+        let mut eval_diagnostics = BTreeMap::new();
+        eval_diagnostics.insert(
+            NormalPath::new("src/A.hs", &base_dir).unwrap(),
+            vec![mismatch_a.clone()],
+        );
+        eval_diagnostics.insert(
+            NormalPath::new("src/B.hs", &base_dir).unwrap(),
+            vec![mismatch_b.clone()],
+        );
+
+        // `B` was evaluated again, so its diagnostic is already in the log; only `A` is added.
+        let warning = make_diagnostic(Severity::Warning, "src/C.hs", "Unused import");
+        let log = make_compilation_log(
+            vec![make_compiling_module("B", "src/B.hs")],
+            vec![warning.clone(), mismatch_b.clone()],
+            CompilationResult::Ok,
+        );
+        assert_eq!(
+            with_eval_diagnostics(&eval_diagnostics, &log).diagnostics,
+            vec![warning, mismatch_b, mismatch_a]
+        );
+
+        // Eval commands don't run when compilation fails.
+        let log = make_compilation_log(vec![], vec![], CompilationResult::Err);
+        assert!(matches!(
+            with_eval_diagnostics(&eval_diagnostics, &log),
+            Cow::Borrowed(log) if log.diagnostics.is_empty()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_console_writer_lsp_stdio_with_output_file() {
+        use clap::Parser;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("ghci.txt");
+        let opts = Opts::try_parse_from([
+            "ghciwatch",
+            "--lsp",
+            "stdio",
+            "--output",
+            output_path.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        // `stdout` is reserved for LSP messages, so `ghci` output is duplicated to `stderr`.
+        let writer = format!("{:?}", GhciOpts::console_writer(&opts).await.unwrap());
+        assert!(writer.contains("Tee"), "{writer}");
+        assert!(writer.contains("Stderr"), "{writer}");
+        assert!(!writer.contains("Stdout"), "{writer}");
+    }
 }
@@ -1,26 +1,44 @@
 use std::collections::VecDeque;
 use std::fmt::Display;
 use std::ops::Range;
+use std::time::Duration;
 
+use camino::Utf8Path;
 use line_span::LineSpanExt;
 use miette::miette;
 use winnow::ascii::line_ending;
 use winnow::ascii::space0;
+use winnow::ascii::till_line_ending;
 use winnow::combinator::alt;
 use winnow::combinator::opt;
 use winnow::combinator::peek;
 use winnow::combinator::repeat;
 use winnow::combinator::repeat_till;
+use winnow::error::ContextError;
+use winnow::error::ErrMode;
+use winnow::stream::Location;
 use winnow::Located;
 use winnow::PResult;
 use winnow::Parser;
 
 use crate::ghci::parse::lines::line_ending_or_eof;
+use crate::ghci::parse::GhcDiagnostic;
+use crate::ghci::parse::PositionRange;
+use crate::ghci::parse::Severity;
 use crate::ghci::GhciCommand;
 
 use super::lines::rest_of_line;
 use super::lines::until_newline;
 
+/// The syntax an [`EvalCommand`] was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvalSyntax {
+    /// An eval comment, like `-- $> expr`, with expected output in `-- <$` lines.
+    Eval,
+    /// A Haddock example, like `-- >>> expr`, with expected output in the following comment lines.
+    Doctest,
+}
+
 /// A (Haskell) command for `ghciwatch` to evaluate in `ghci`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EvalCommand {
@@ -35,13 +53,128 @@ pub struct EvalCommand {
     column: usize,
     /// The byte offsets corresponding to the span this command is from.
     byte_span: Range<usize>,
+    /// The expected output, from `-- <$` lines after the command.
+    pub expected: Option<String>,
+    /// The byte offsets of the expected output lines, or an empty span where they would go.
+    expected_span: Range<usize>,
+    /// The syntax this command was written in.
+    syntax: EvalSyntax,
+}
+
+impl EvalCommand {
+    /// Check the command's output against its expected output, if it has any.
+    ///
+    /// Trailing whitespace and leading and trailing blank lines are ignored. Returns a diagnostic
+    /// if the output doesn't match.
+    pub fn check_output(&self, path: &Utf8Path, output: &str) -> Option<GhcDiagnostic> {
+        let expected = normalize_output(self.expected.as_deref()?);
+        let actual = normalize_output(&strip_ansi_escapes::strip_str(output));
+        if expected == actual {
+            return None;
+        }
+
+        let indent = |text: &str| {
+            if text.is_empty() {
+                return "        (no output)".to_owned();
+            }
+            text.lines()
+                .map(|line| format!("        {line}").trim_end().to_owned())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Some(GhcDiagnostic {
+            severity: Severity::Error,
+            path: Some(path.to_owned()),
+            span: PositionRange::new(self.line, self.column, self.line, self.column),
+            message: format!(
+                "\n    • Eval output doesn't match the expected output for `{}`\n    \
+                 Expected:\n{}\n    Actual:\n{}\n",
+                self.display_command,
+                indent(&expected),
+                indent(&actual),
+            ),
+            ..Default::default()
+        })
+    }
+
+    /// Get a diagnostic reporting that the command was interrupted after running for `timeout`.
+    pub fn timed_out(&self, path: &Utf8Path, timeout: Duration) -> GhcDiagnostic {
+        GhcDiagnostic {
+            severity: Severity::Error,
+            path: Some(path.to_owned()),
+            span: PositionRange::new(self.line, self.column, self.line, self.column),
+            message: format!(
+                "\n    • Eval command `{}` timed out after {}\n",
+                self.display_command,
+                humantime::format_duration(timeout),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rewrite `contents` so that each eval command is followed by its output as `-- <$` lines,
+/// replacing any expected output already there.
+///
+/// The `results` pair commands parsed from `contents` with their output.
+pub fn write_eval_results(contents: &str, results: &[(EvalCommand, String)]) -> String {
+    let mut results = results.iter().collect::<Vec<_>>();
+    results.sort_by_key(|(command, _output)| command.expected_span.start);
+
+    let mut new_contents = String::with_capacity(contents.len());
+    let mut offset = 0;
+    for (command, output) in results {
+        let span = &command.expected_span;
+        new_contents.push_str(&contents[offset..span.start]);
+        offset = span.end;
+
+        let output = normalize_output(&strip_ansi_escapes::strip_str(output));
+        if output.is_empty() {
+            continue;
+        }
+        if !new_contents.is_empty() && !new_contents.ends_with('\n') {
+            // The command is at the end of a file with no trailing newline.
+            new_contents.push('\n');
+        }
+        // Indent the output like the last line of the eval comment.
+        let comment = contents[..span.start].trim_end_matches(['\r', '\n']);
+        let last_line = &comment[comment.rfind('\n').map_or(0, |i| i + 1)..];
+        let indent = &last_line[..last_line.len() - last_line.trim_start().len()];
+        for line in output.lines() {
+            let line = match command.syntax {
+                EvalSyntax::Eval => format!("{indent}-- <$ {line}"),
+                EvalSyntax::Doctest if line.is_empty() => format!("{indent}-- <BLANKLINE>"),
+                EvalSyntax::Doctest => format!("{indent}-- {line}"),
+            };
+            new_contents.push_str(line.trim_end());
+            new_contents.push('\n');
+        }
+    }
+    new_contents.push_str(&contents[offset..]);
+    new_contents
+}
+
+/// Normalize eval output for comparison by removing trailing whitespace and blank lines at the
+/// start and end.
+fn normalize_output(output: &str) -> String {
+    output
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_owned()
 }
 
 impl Display for EvalCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let marker = match self.syntax {
+            EvalSyntax::Eval => "-- $>",
+            EvalSyntax::Doctest => "-- >>>",
+        };
         write!(
             f,
-            "{}:{}: -- $> {}",
+            "{}:{}: {marker} {}",
             self.line, self.column, self.display_command
         )
     }
@@ -53,11 +186,16 @@ struct ByteSpanCommand {
     command: GhciCommand,
     display_command: String,
     span: Range<usize>,
+    expected: Option<String>,
+    expected_span: Range<usize>,
+    syntax: EvalSyntax,
 }
 
 /// Parse Haskell source file contents into a `Vec` of [`EvalCommand`]s to evaluate on reloads.
-pub fn parse_eval_commands(contents: &str) -> miette::Result<Vec<EvalCommand>> {
-    let mut byte_commands = eval_commands
+///
+/// If `doctests` is true, Haddock examples like `-- >>> expr` are parsed as eval commands too.
+pub fn parse_eval_commands(contents: &str, doctests: bool) -> miette::Result<Vec<EvalCommand>> {
+    let mut byte_commands = eval_commands(doctests)
         .parse(Located::new(contents))
         .map_err(|err| miette!("{err}"))?;
 
@@ -101,6 +239,9 @@ pub fn parse_eval_commands(contents: &str) -> miette::Result<Vec<EvalCommand>> {
                 line: line_number,
                 column: column_number,
                 byte_span: byte_command.span,
+                expected: byte_command.expected,
+                expected_span: byte_command.expected_span,
+                syntax: byte_command.syntax,
             });
 
             // Start working on the next command.
@@ -115,17 +256,37 @@ pub fn parse_eval_commands(contents: &str) -> miette::Result<Vec<EvalCommand>> {
 }
 
 /// Parse file contents into eval commands.
-fn eval_commands(input: &mut Located<&str>) -> PResult<VecDeque<ByteSpanCommand>> {
+fn eval_commands<'i>(
+    doctests: bool,
+) -> impl Parser<Located<&'i str>, VecDeque<ByteSpanCommand>, ContextError> {
     enum Item {
         Command(ByteSpanCommand),
         Ignore,
     }
 
+    let doctest = move |input: &mut Located<&'i str>| {
+        if doctests {
+            doctest_command.parse_next(input)
+        } else {
+            Err(ErrMode::Backtrack(ContextError::new()))
+        }
+    };
+
     repeat(
         0..,
         alt((
-            line_eval_command.map(Item::Command),
-            multiline_eval_command.map(Item::Command),
+            (
+                alt((line_eval_command, multiline_eval_command)),
+                opt(expected_output).with_span(),
+            )
+                .map(|(command, (expected, expected_span))| {
+                    Item::Command(ByteSpanCommand {
+                        expected,
+                        expected_span,
+                        ..command
+                    })
+                }),
+            doctest.map(Item::Command),
             rest_of_line.map(|_| Item::Ignore),
         )),
     )
@@ -136,7 +297,6 @@ fn eval_commands(input: &mut Located<&str>) -> PResult<VecDeque<ByteSpanCommand>
         }
         commands
     })
-    .parse_next(input)
 }
 
 /// Parse a single-line eval command starting with `-- $> `.
@@ -149,11 +309,15 @@ fn line_eval_command(input: &mut Located<&str>) -> PResult<ByteSpanCommand> {
     let _ = space0.parse_next(input)?;
     let (command, span) = until_newline.with_span().parse_next(input)?;
     let command: GhciCommand = command.to_owned().into();
+    let end = input.location();
 
     Ok(ByteSpanCommand {
         display_command: command.clone().into(),
         command,
         span,
+        expected: None,
+        expected_span: end..end,
+        syntax: EvalSyntax::Eval,
     })
 }
 
@@ -178,6 +342,7 @@ fn multiline_eval_command(input: &mut Located<&str>) -> PResult<ByteSpanCommand>
             .parse_next(input)?;
     multiline_eval_end.parse_next(input)?;
     let _ = (space0, line_ending_or_eof).parse_next(input)?;
+    let end = input.location();
 
     Ok(ByteSpanCommand {
         // `command` ends with a newline so we put a newline after the `:{` but not before the
@@ -185,9 +350,95 @@ fn multiline_eval_command(input: &mut Located<&str>) -> PResult<ByteSpanCommand>
         command: format!(":{{\n{command}:}}").into(),
         display_command: command.trim().to_owned(),
         span,
+        expected: None,
+        expected_span: end..end,
+        syntax: EvalSyntax::Eval,
     })
 }
 
+/// Parse a Haddock example starting with `-- >>> `, followed by its expected output.
+///
+/// Multi-line examples start with `-- >>> :{` and end with `-- :}`, with the lines in between
+/// written as regular comments. The expected output is the comment lines after the example, up
+/// to a blank comment line or the next example. Unlike eval comments, examples without any
+/// expected output are expected to produce no output.
+fn doctest_command(input: &mut Located<&str>) -> PResult<ByteSpanCommand> {
+    let _ = (space0, "-- >>>", space0).parse_next(input)?;
+    let (first_line, first_span) = until_newline
+        .verify(|line: &str| !line.trim().is_empty())
+        .with_span()
+        .parse_next(input)?;
+
+    let (command, display_command, span) = if first_line.trim_end() == ":{" {
+        let (lines, _end): (Vec<&str>, _) = repeat_till(
+            0..,
+            comment_line,
+            comment_line.verify(|line: &str| line.trim() == ":}"),
+        )
+        .parse_next(input)?;
+        let lines = lines.join("\n");
+        (
+            format!(":{{\n{lines}\n:}}").into(),
+            lines.trim().to_owned(),
+            first_span.start..input.location(),
+        )
+    } else {
+        let command = first_line.trim_end().to_owned();
+        (command.clone().into(), command, first_span)
+    };
+
+    let (expected, expected_span) = repeat(
+        0..,
+        comment_line
+            .verify(|line: &str| !line.trim().is_empty() && !line.trim_start().starts_with(">>>"))
+            .map(|line: &str| {
+                if line.trim() == "<BLANKLINE>" {
+                    ""
+                } else {
+                    line
+                }
+            }),
+    )
+    .map(|lines: Vec<&str>| lines.join("\n"))
+    .with_span()
+    .parse_next(input)?;
+
+    Ok(ByteSpanCommand {
+        command,
+        display_command,
+        span,
+        expected: Some(expected),
+        expected_span,
+        syntax: EvalSyntax::Doctest,
+    })
+}
+
+/// Parse a line comment starting with `--`, returning the text after it.
+///
+/// A single space after the `--` is removed.
+fn comment_line<'i>(input: &mut Located<&'i str>) -> PResult<&'i str> {
+    let _ = (space0, "--", opt(' ')).parse_next(input)?;
+    let line = till_line_ending.parse_next(input)?;
+    let _ = line_ending_or_eof.parse_next(input)?;
+    Ok(line)
+}
+
+/// Parse the expected output for an eval command, written as lines starting with `-- <$`.
+///
+/// A single space after the marker is removed, so `-- <$ 1` expects the output `1`.
+fn expected_output(input: &mut Located<&str>) -> PResult<String> {
+    fn expected_output_line<'i>(input: &mut Located<&'i str>) -> PResult<&'i str> {
+        let _ = (space0, "-- <$", opt(' ')).parse_next(input)?;
+        let line = till_line_ending.parse_next(input)?;
+        let _ = line_ending_or_eof.parse_next(input)?;
+        Ok(line)
+    }
+
+    repeat(1.., expected_output_line)
+        .map(|lines: Vec<&str>| lines.join("\n"))
+        .parse_next(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,6 +456,9 @@ mod tests {
                 command: "foo".to_owned().into(),
                 display_command: "foo".into(),
                 span: 6..10,
+                expected: None,
+                expected_span: 10..10,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -214,6 +468,9 @@ mod tests {
                 command: "foo".to_owned().into(),
                 display_command: "foo".into(),
                 span: 5..9,
+                expected: None,
+                expected_span: 9..9,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -226,6 +483,9 @@ mod tests {
                 command: "foo".to_owned().into(),
                 display_command: "foo".into(),
                 span: 9..13,
+                expected: None,
+                expected_span: 13..13,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -268,6 +528,9 @@ mod tests {
                 .into(),
                 display_command: "foo".into(),
                 span: 6..10,
+                expected: None,
+                expected_span: 16..16,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -304,6 +567,9 @@ mod tests {
                 )
                 .into(),
                 span: 6..28,
+                expected: None,
+                expected_span: 34..34,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -327,6 +593,9 @@ mod tests {
                 .into(),
                 display_command: "puppy".into(),
                 span: 11..17,
+                expected: None,
+                expected_span: 23..23,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -350,6 +619,9 @@ mod tests {
                 .into(),
                 display_command: "puppy".into(),
                 span: 9..19,
+                expected: None,
+                expected_span: 25..25,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -375,6 +647,9 @@ mod tests {
                 .into(),
                 display_command: "puppy\ndoggy".into(),
                 span: 6..18,
+                expected: None,
+                expected_span: 32..32,
+                syntax: EvalSyntax::Eval,
             }
         );
 
@@ -438,13 +713,16 @@ mod tests {
     #[test]
     fn test_parse_eval_command_no_eol() {
         assert_eq!(
-            parse_eval_commands("-- $> foo").unwrap(),
+            parse_eval_commands("-- $> foo", false).unwrap(),
             vec![EvalCommand {
                 command: "foo".to_owned().into(),
                 display_command: "foo".to_owned(),
                 line: 1,
                 column: 7,
                 byte_span: 6..9,
+                expected: None,
+                expected_span: 9..9,
+                syntax: EvalSyntax::Eval,
             },]
         )
     }
@@ -452,13 +730,16 @@ mod tests {
     #[test]
     fn test_parse_multiline_eval_command_no_eol() {
         assert_eq!(
-            parse_eval_commands("{- $>\nfoo\n<$ -}").unwrap(),
+            parse_eval_commands("{- $>\nfoo\n<$ -}", false).unwrap(),
             vec![EvalCommand {
                 command: ":{\nfoo\n:}".to_owned().into(),
                 display_command: "foo".to_owned(),
                 line: 2,
                 column: 1,
                 byte_span: 6..10,
+                expected: None,
+                expected_span: 15..15,
+                syntax: EvalSyntax::Eval,
             },]
         )
     }
@@ -466,8 +747,9 @@ mod tests {
     #[test]
     fn test_parse_eval_commands() {
         assert_eq!(
-            parse_eval_commands(indoc!(
-                r#"
+            parse_eval_commands(
+                indoc!(
+                    r#"
                 module Foo where
 
                 -- $> myFunc 0
@@ -489,7 +771,9 @@ mod tests {
                     <$ -}
                     0
                 "#
-            ))
+                ),
+                false
+            )
             .unwrap(),
             vec![
                 EvalCommand {
@@ -498,6 +782,9 @@ mod tests {
                     line: 3,
                     column: 7,
                     byte_span: 24..33,
+                    expected: None,
+                    expected_span: 33..33,
+                    syntax: EvalSyntax::Eval,
                 },
                 EvalCommand {
                     command: ":{\nhello\n:}".to_owned().into(),
@@ -505,6 +792,9 @@ mod tests {
                     line: 8,
                     column: 1,
                     byte_span: 73..79,
+                    expected: None,
+                    expected_span: 85..85,
+                    syntax: EvalSyntax::Eval,
                 },
                 EvalCommand {
                     command: "goodbye".to_owned().into(),
@@ -512,6 +802,9 @@ mod tests {
                     line: 10,
                     column: 7,
                     byte_span: 91..99,
+                    expected: None,
+                    expected_span: 99..99,
+                    syntax: EvalSyntax::Eval,
                 },
                 EvalCommand {
                     command: ":{\n    but this does!\n:}".to_owned().into(),
@@ -519,8 +812,219 @@ mod tests {
                     line: 18,
                     column: 1,
                     byte_span: 190..209,
+                    expected: None,
+                    expected_span: 219..219,
+                    syntax: EvalSyntax::Eval,
                 },
             ]
         )
     }
+
+    #[test]
+    fn test_parse_expected_output() {
+        assert_eq!(
+            parse_eval_commands(
+                indoc!(
+                    "
+                -- $> myFunc 0
+                -- <$ 0
+                myFunc :: Int -> Int
+                myFunc = id
+
+                {- $>
+                map myFunc [1, 2]
+                <$ -}
+                  -- <$ [1,
+                  -- <$
+                  -- <$  2]
+                -- $> goodbye
+                -- <$ hello"
+                ),
+                false
+            )
+            .unwrap()
+            .into_iter()
+            .map(|command| (command.display_command, command.expected))
+            .collect::<Vec<_>>(),
+            vec![
+                ("myFunc 0".to_owned(), Some("0".to_owned())),
+                (
+                    "map myFunc [1, 2]".to_owned(),
+                    Some("[1,\n\n 2]".to_owned())
+                ),
+                ("goodbye".to_owned(), Some("hello".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_output() {
+        let command = parse_eval_commands("-- $> myFunc 0\n-- <$ 0\n", false)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let path = Utf8Path::new("src/MyLib.hs");
+
+        assert_eq!(command.check_output(path, "0\n"), None);
+        assert_eq!(command.check_output(path, "\n0   \n\n"), None);
+
+        let diagnostic = command.check_output(path, "1\n").unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.path, Some(path.to_owned()));
+        assert_eq!(diagnostic.span, PositionRange::new(1, 7, 1, 7));
+        assert_eq!(
+            diagnostic.message,
+            "\n    • Eval output doesn't match the expected output for `myFunc 0`\n    \
+             Expected:\n        0\n    Actual:\n        1\n"
+        );
+
+        // Commands without expected output always pass.
+        let command = parse_eval_commands("-- $> myFunc 0\n", false)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(command.check_output(path, "1\n"), None);
+
+        let diagnostic = command.timed_out(path, Duration::from_secs(10));
+        assert_eq!(diagnostic.span, PositionRange::new(1, 7, 1, 7));
+        assert_eq!(
+            diagnostic.message,
+            "\n    • Eval command `myFunc 0` timed out after 10s\n"
+        );
+    }
+
+    #[test]
+    fn test_write_eval_results() {
+        let contents = indoc!(
+            "
+            module MyLib where
+
+            -- $> myFunc 0
+            myFunc :: Int -> Int
+            myFunc = id
+
+              {- $>
+              map myFunc [1, 2]
+              <$ -}
+              -- <$ [3, 4]
+              -- <$ [5, 6]
+
+            -- $> putStrLn \"\"
+            -- <$ stale
+            -- $> myFunc 1"
+        );
+        let commands = parse_eval_commands(contents, false).unwrap();
+        let results = commands
+            .into_iter()
+            .zip(["0\n", "[1,2]\n", "\n", "\x1b[1m1\x1b[0m\n"])
+            .map(|(command, output)| (command, output.to_owned()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            write_eval_results(contents, &results),
+            indoc!(
+                "
+                module MyLib where
+
+                -- $> myFunc 0
+                -- <$ 0
+                myFunc :: Int -> Int
+                myFunc = id
+
+                  {- $>
+                  map myFunc [1, 2]
+                  <$ -}
+                  -- <$ [1,2]
+
+                -- $> putStrLn \"\"
+                -- $> myFunc 1
+                -- <$ 1
+                "
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_doctests() {
+        let contents = indoc!(
+            "
+            -- | Double a number.
+            --
+            -- >>> double 2
+            -- 4
+            --
+            -- >>> let x = 1
+            -- >>> :{
+            -- double
+            --   x
+            -- :}
+            -- 2
+            -- >>> putStrLn \"a\\n\\nb\"
+            -- a
+            -- <BLANKLINE>
+            -- b
+            double :: Int -> Int
+            double = (* 2)
+
+            -- $> double 3
+            "
+        );
+
+        assert_eq!(
+            parse_eval_commands(contents, true)
+                .unwrap()
+                .into_iter()
+                .map(|command| (command.line, command.command.to_string(), command.expected))
+                .collect::<Vec<_>>(),
+            vec![
+                (3, "double 2".to_owned(), Some("4".to_owned())),
+                (6, "let x = 1".to_owned(), Some("".to_owned())),
+                (7, ":{\ndouble\n  x\n:}".to_owned(), Some("2".to_owned())),
+                (
+                    12,
+                    "putStrLn \"a\\n\\nb\"".to_owned(),
+                    Some("a\n\nb".to_owned())
+                ),
+                (19, "double 3".to_owned(), None),
+            ]
+        );
+
+        // Examples are only parsed if enabled.
+        assert_eq!(parse_eval_commands(contents, false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_write_doctest_results() {
+        let contents = indoc!(
+            "
+            -- >>> double 2
+            -- 5
+            --
+            -- >>> putStrLn \"a\\n\\nb\"
+            double :: Int -> Int
+            "
+        );
+        let commands = parse_eval_commands(contents, true).unwrap();
+        let results = commands
+            .into_iter()
+            .zip(["4\n", "a\n\nb\n"])
+            .map(|(command, output)| (command, output.to_owned()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            write_eval_results(contents, &results),
+            indoc!(
+                "
+                -- >>> double 2
+                -- 4
+                --
+                -- >>> putStrLn \"a\\n\\nb\"
+                -- a
+                -- <BLANKLINE>
+                -- b
+                double :: Int -> Int
+                "
+            )
+        );
+    }
 }
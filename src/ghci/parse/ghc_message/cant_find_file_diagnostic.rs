@@ -28,6 +28,9 @@ pub fn cant_find_file_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
         path: Some(Utf8PathBuf::from(path)),
         span: Default::default(),
         message: "can't find file".to_owned(),
+        code: None,
+        flags: vec![],
+        hints: vec![],
     })
 }
 
@@ -49,7 +52,8 @@ mod tests {
                 severity: Severity::Error,
                 path: Some("Why.hs".into()),
                 span: Default::default(),
-                message: "can't find file".to_owned()
+                message: "can't find file".to_owned(),
+                ..Default::default()
             }
         );
 
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use serde::Serialize;
 use winnow::ascii::digit1;
 use winnow::combinator::alt;
 use winnow::combinator::opt;
@@ -20,7 +21,7 @@ use crate::ghci::parse::CompilationResult;
 /// ```text
 /// Failed, 58 modules loaded.
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct CompilationSummary {
     /// The compilation result; whether compilation succeeded or failed.
     pub result: CompilationResult,
@@ -28,7 +29,8 @@ pub struct CompilationSummary {
     pub modules_loaded: ModulesLoaded,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ModulesLoaded {
     /// The count of modules loaded.
     Count(usize),
@@ -9,17 +9,25 @@ use crate::ghci::parse::module_and_files;
 
 /// Parse a `[1 of 3] Compiling Foo ( Foo.hs, Foo.o, interpreted )` message.
 pub fn compiling(input: &mut &str) -> PResult<CompilingModule> {
+    compiling_numbered
+        .map(|(_index, _total, module)| module)
+        .parse_next(input)
+}
+
+/// Parse a `[1 of 3] Compiling Foo ( Foo.hs, Foo.o, interpreted )` message, returning the module's
+/// index, the total number of modules being compiled, and the module.
+pub fn compiling_numbered(input: &mut &str) -> PResult<(usize, usize, CompilingModule)> {
     let _ = "[".parse_next(input)?;
     let _ = space0.parse_next(input)?;
-    let _ = digit1.parse_next(input)?;
+    let index = digit1.parse_to().parse_next(input)?;
     let _ = " of ".parse_next(input)?;
-    let _ = digit1.parse_next(input)?;
+    let total = digit1.parse_to().parse_next(input)?;
     let _ = "]".parse_next(input)?;
     let _ = " Compiling ".parse_next(input)?;
     let module = module_and_files.parse_next(input)?;
     let _ = rest_of_line.parse_next(input)?;
 
-    Ok(module)
+    Ok((index, total, module))
 }
 
 #[cfg(test)]
@@ -66,6 +74,20 @@ mod tests {
             }
         );
 
+        assert_eq!(
+            compiling_numbered
+                .parse("[  12 of 2000] Compiling MyLib ( src/MyLib.hs, interpreted )\n")
+                .unwrap(),
+            (
+                12,
+                2000,
+                CompilingModule {
+                    name: "MyLib".into(),
+                    path: "src/MyLib.hs".into()
+                }
+            )
+        );
+
         // Shouldn't parse multiple lines.
         assert!(compiling
             .parse(indoc!(
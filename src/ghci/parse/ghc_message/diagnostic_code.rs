@@ -0,0 +1,136 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use miette::miette;
+use serde::Serialize;
+
+/// A GHC diagnostic code, like `GHC-83865`.
+///
+/// See: <https://errors.haskell.org/>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DiagnosticCode(u32);
+
+impl DiagnosticCode {
+    /// Construct a diagnostic code from its number, like `83865` for `GHC-83865`.
+    pub fn new(code: u32) -> Self {
+        Self(code)
+    }
+
+    /// The URL for this code's page in the Haskell Error Index.
+    pub fn url(&self) -> String {
+        format!("https://errors.haskell.org/messages/{self}")
+    }
+}
+
+impl Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GHC-{:05}", self.0)
+    }
+}
+
+impl FromStr for DiagnosticCode {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix("GHC-")
+            .filter(|code| !code.is_empty() && code.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|code| code.parse().ok())
+            .map(Self)
+            .ok_or_else(|| miette!("Invalid GHC diagnostic code: {s:?}"))
+    }
+}
+
+impl Serialize for DiagnosticCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Parse the bracketed tags on the first line of a diagnostic message into a diagnostic code and
+/// warning flags.
+///
+/// ```text
+/// [GHC-66111] [-Wunused-imports, Werror=unused-imports]
+/// ```
+pub fn parse_message_tags(message: &str) -> (Option<DiagnosticCode>, Vec<String>) {
+    let tags = message
+        .trim_start_matches(' ')
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split('[')
+        .skip(1)
+        .filter_map(|tag| tag.split_once(']'))
+        .map(|(tag, _)| tag);
+
+    let mut code = None;
+    let mut flags = Vec::new();
+    for tag in tags {
+        match tag.parse::<DiagnosticCode>() {
+            Ok(parsed) => {
+                code.get_or_insert(parsed);
+            }
+            Err(_) => flags.extend(
+                tag.split(", ")
+                    .filter(|flag| flag.starts_with('-'))
+                    .map(ToOwned::to_owned),
+            ),
+        }
+    }
+    (code, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_diagnostic_code() {
+        assert_eq!(DiagnosticCode::new(158).to_string(), "GHC-00158");
+        assert_eq!(
+            "GHC-00158".parse::<DiagnosticCode>().unwrap(),
+            DiagnosticCode::new(158)
+        );
+        assert_eq!(
+            DiagnosticCode::new(83865).url(),
+            "https://errors.haskell.org/messages/GHC-83865"
+        );
+        assert!("GHC-".parse::<DiagnosticCode>().is_err());
+        assert!("GHC-12a".parse::<DiagnosticCode>().is_err());
+        assert!("-Wunused-imports".parse::<DiagnosticCode>().is_err());
+    }
+
+    #[test]
+    fn test_parse_message_tags() {
+        assert_eq!(
+            parse_message_tags("[GHC-00158]\n    • Can't make a derived instance"),
+            (Some(DiagnosticCode::new(158)), vec![])
+        );
+        assert_eq!(
+            parse_message_tags(" [GHC-40910] [-Wunused-top-binds]\n    Defined but not used"),
+            (
+                Some(DiagnosticCode::new(40910)),
+                vec!["-Wunused-top-binds".to_owned()]
+            )
+        );
+        assert_eq!(
+            parse_message_tags(
+                "[GHC-66111] [-Wunused-imports, Werror=unused-imports]\n    The import is redundant"
+            ),
+            (
+                Some(DiagnosticCode::new(66111)),
+                vec!["-Wunused-imports".to_owned()]
+            )
+        );
+        assert_eq!(
+            parse_message_tags("[-Wunused-imports]\n    The import of ‘Data.List’ is redundant"),
+            (None, vec!["-Wunused-imports".to_owned()])
+        );
+        assert_eq!(
+            parse_message_tags("\n    • Couldn't match type [Char] with ()"),
+            (None, vec![])
+        );
+    }
+}
@@ -3,6 +3,7 @@ use winnow::ascii::space1;
 use winnow::PResult;
 use winnow::Parser;
 
+use crate::ghci::parse::ghc_message::diagnostic_code::parse_message_tags;
 use crate::ghci::parse::ghc_message::message_body::parse_message_body;
 use crate::ghci::parse::ghc_message::path_colon;
 use crate::ghci::parse::ghc_message::position;
@@ -30,12 +31,16 @@ pub fn generic_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
     let severity = severity::parse_severity_colon.parse_next(input)?;
     let _ = space0.parse_next(input)?;
     let message = parse_message_body.parse_next(input)?;
+    let (code, flags) = parse_message_tags(message);
 
     Ok(GhcDiagnostic {
         severity,
         path: Some(path.to_owned()),
         span,
         message: message.to_owned(),
+        code,
+        flags,
+        hints: vec![],
     })
 }
 
@@ -43,6 +48,7 @@ pub fn generic_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
 mod tests {
     use super::*;
 
+    use crate::ghci::parse::ghc_message::DiagnosticCode;
     use indoc::indoc;
     use position::PositionRange;
     use pretty_assertions::assert_eq;
@@ -79,7 +85,9 @@ mod tests {
                       |            ^^^^^^^
                     "
                 )
-                .into()
+                .into(),
+                code: Some(DiagnosticCode::new(158)),
+                ..Default::default()
             }
         );
 
@@ -101,6 +109,30 @@ mod tests {
             .is_err(),);
     }
 
+    #[test]
+    fn test_parse_diagnostic_flags() {
+        assert_eq!(
+            generic_diagnostic
+                .parse(
+                    "src/MyLib.hs:3:1-16: error: [GHC-66111] \
+                     [-Wunused-imports, Werror=unused-imports]\n    \
+                     The import of ‘Data.List’ is redundant\n"
+                )
+                .unwrap(),
+            GhcDiagnostic {
+                severity: Severity::Error,
+                path: Some("src/MyLib.hs".into()),
+                span: PositionRange::new(3, 1, 3, 16),
+                message: "[GHC-66111] [-Wunused-imports, Werror=unused-imports]\n    \
+                          The import of ‘Data.List’ is redundant\n"
+                    .into(),
+                code: Some(DiagnosticCode::new(66111)),
+                flags: vec!["-Wunused-imports".into()],
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn test_diagnostic_display() {
         assert_eq!(
@@ -120,7 +152,8 @@ mod tests {
                     "  |           ^^^^^^^^^",
                     "",
                 ]
-                .join("\n")
+                .join("\n"),
+                ..Default::default()
             }
             .to_string(),
             indoc!(
@@ -0,0 +1,269 @@
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use winnow::combinator::peek;
+use winnow::PResult;
+use winnow::Parser;
+
+use crate::ghci::parse::lines::until_newline;
+
+use super::DiagnosticCode;
+use super::GhcDiagnostic;
+use super::PositionRange;
+use super::Severity;
+
+/// A diagnostic emitted by GHC with `-fdiagnostics-as-json`.
+///
+/// Fields we don't use (like `version` and `ghcVersion`) are ignored.
+#[derive(Debug, Deserialize)]
+struct JsonDiagnostic {
+    span: Option<JsonSpan>,
+    severity: JsonSeverity,
+    code: Option<u32>,
+    message: Vec<String>,
+    #[serde(default)]
+    hints: Vec<String>,
+    #[serde(default)]
+    reason: Option<JsonReason>,
+}
+
+#[derive(Debug, Deserialize)]
+enum JsonSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonSpan {
+    file: Utf8PathBuf,
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonPosition {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonReason {
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+impl JsonSpan {
+    /// Convert to a [`PositionRange`] as GHC would display it.
+    ///
+    /// GHC's JSON end columns are exclusive, but the end columns in its human-readable output are
+    /// inclusive.
+    fn position_range(&self) -> PositionRange {
+        let end_column = if self.start.line == self.end.line {
+            self.end.column.saturating_sub(1).max(self.start.column)
+        } else {
+            self.end.column.saturating_sub(1)
+        };
+        PositionRange::new(
+            self.start.line,
+            self.start.column,
+            self.end.line,
+            end_column,
+        )
+    }
+}
+
+impl From<JsonDiagnostic> for GhcDiagnostic {
+    fn from(diagnostic: JsonDiagnostic) -> Self {
+        let code = diagnostic.code.map(DiagnosticCode::new);
+        let flags = diagnostic
+            .reason
+            .map(|reason| {
+                reason
+                    .flags
+                    .into_iter()
+                    .map(|flag| format!("-W{flag}"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // Render the message like GHC's human-readable output, so that the error log and console
+        // output look the same regardless of which format GHC used.
+        let mut message = String::new();
+        let mut tags = Vec::new();
+        if let Some(code) = code {
+            tags.push(format!("[{code}]"));
+        }
+        if !flags.is_empty() {
+            tags.push(format!("[{}]", flags.join(", ")));
+        }
+        message.push_str(&tags.join(" "));
+        if diagnostic.message.len() == 1 {
+            push_indented(&mut message, "    ", "    ", &diagnostic.message[0]);
+        } else {
+            for block in &diagnostic.message {
+                push_indented(&mut message, "    • ", "      ", block);
+            }
+        }
+        match diagnostic.hints.as_slice() {
+            [] => {}
+            [hint] => push_indented(&mut message, "    Suggested fix: ", "      ", hint),
+            hints => {
+                message.push_str("\n    Suggested fixes:");
+                for hint in hints {
+                    push_indented(&mut message, "      • ", "        ", hint);
+                }
+            }
+        }
+        message.push('\n');
+
+        let (path, span) = match diagnostic.span {
+            Some(span) => {
+                let range = span.position_range();
+                (Some(span.file), range)
+            }
+            None => (None, Default::default()),
+        };
+
+        GhcDiagnostic {
+            severity: match diagnostic.severity {
+                JsonSeverity::Warning => Severity::Warning,
+                JsonSeverity::Error => Severity::Error,
+            },
+            path,
+            span,
+            message,
+            code,
+            flags,
+            hints: diagnostic.hints,
+        }
+    }
+}
+
+/// Push a new line onto `message` for each line in `text`, prefixing the first line with `first`
+/// and the rest with `rest`.
+fn push_indented(message: &mut String, first: &str, rest: &str, text: &str) {
+    for (i, line) in text.lines().enumerate() {
+        message.push('\n');
+        message.push_str(if i == 0 { first } else { rest });
+        message.push_str(line);
+    }
+}
+
+/// Parse a diagnostic emitted by GHC with `-fdiagnostics-as-json`, like this (on one line):
+///
+/// ```json
+/// {"version":"1.1","ghcVersion":"ghc-9.12.1",
+///  "span":{"file":"src/MyModule.hs","start":{"line":4,"column":11},"end":{"line":4,"column":20}},
+///  "severity":"Error","code":83865,
+///  "message":["Couldn't match type ‘[Char]’ with ‘()’\nExpected: ()\n  Actual: String"],
+///  "hints":[],"reason":null}
+/// ```
+pub fn json_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
+    let _ = peek("{").parse_next(input)?;
+    until_newline
+        .verify_map(|line: &str| {
+            serde_json::from_str::<JsonDiagnostic>(line)
+                .map_err(|err| tracing::debug!(line, "Failed to parse JSON diagnostic: {err}"))
+                .ok()
+        })
+        .map(GhcDiagnostic::from)
+        .parse_next(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_json_diagnostic() {
+        assert_eq!(
+            json_diagnostic
+                .parse(concat!(
+                    r#"{"version":"1.1","ghcVersion":"ghc-9.12.1","#,
+                    r#""span":{"file":"src/MyModule.hs","start":{"line":4,"column":11},"end":{"line":4,"column":20}},"#,
+                    r#""severity":"Error","code":83865,"#,
+                    r#""message":["Couldn't match type ‘[Char]’ with ‘()’\nExpected: ()\n  Actual: String","#,
+                    r#""In the expression: \"example\""],"#,
+                    r#""hints":[],"reason":null}"#,
+                    "\n"
+                ))
+                .unwrap(),
+            GhcDiagnostic {
+                severity: Severity::Error,
+                path: Some("src/MyModule.hs".into()),
+                span: PositionRange::new(4, 11, 4, 19),
+                message: indoc!(
+                    "
+                    [GHC-83865]
+                        • Couldn't match type ‘[Char]’ with ‘()’
+                          Expected: ()
+                            Actual: String
+                        • In the expression: \"example\"
+                    "
+                )
+                .trim_start()
+                .into(),
+                code: Some(DiagnosticCode::new(83865)),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_json_diagnostic_warning() {
+        assert_eq!(
+            json_diagnostic
+                .parse(concat!(
+                    r#"{"version":"1.1","ghcVersion":"ghc-9.12.1","#,
+                    r#""span":{"file":"src/MyLib.hs","start":{"line":3,"column":1},"end":{"line":3,"column":17}},"#,
+                    r#""severity":"Warning","code":66111,"#,
+                    r#""message":["The import of ‘Data.List’ is redundant"],"#,
+                    r#""hints":["Remove the import"],"reason":{"flags":["unused-imports"]}}"#,
+                ))
+                .unwrap(),
+            GhcDiagnostic {
+                severity: Severity::Warning,
+                path: Some("src/MyLib.hs".into()),
+                span: PositionRange::new(3, 1, 3, 16),
+                message: indoc!(
+                    "
+                    [GHC-66111] [-Wunused-imports]
+                        The import of ‘Data.List’ is redundant
+                        Suggested fix: Remove the import
+                    "
+                )
+                .trim_start()
+                .into(),
+                code: Some(DiagnosticCode::new(66111)),
+                flags: vec!["-Wunused-imports".into()],
+                hints: vec!["Remove the import".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_json_diagnostic_no_span() {
+        assert_eq!(
+            json_diagnostic
+                .parse(concat!(
+                    r#"{"span":null,"severity":"Error","code":null,"#,
+                    r#""message":["Could not find module ‘Example’"]}"#,
+                    "\n"
+                ))
+                .unwrap(),
+            GhcDiagnostic {
+                severity: Severity::Error,
+                path: None,
+                span: Default::default(),
+                message: "\n    Could not find module ‘Example’\n".into(),
+                ..Default::default()
+            }
+        );
+
+        // Not a diagnostic.
+        assert!(json_diagnostic.parse("{\"puppy\":1}\n").is_err());
+        assert!(json_diagnostic.parse("src/MyLib.hs:1:1: error:\n").is_err());
+    }
+}
@@ -6,16 +6,21 @@ use camino::Utf8PathBuf;
 use miette::miette;
 use owo_colors::OwoColorize;
 use owo_colors::Stream::Stdout;
+use serde::Serialize;
 use winnow::combinator::alt;
 use winnow::combinator::repeat;
 use winnow::prelude::*;
 
 mod position;
+pub use position::Position;
 pub use position::PositionRange;
 
 mod severity;
 pub use severity::Severity;
 
+mod diagnostic_code;
+pub use diagnostic_code::DiagnosticCode;
+
 mod single_quote;
 
 mod path_colon;
@@ -23,6 +28,7 @@ use path_colon::path_colon;
 
 mod compiling;
 use compiling::compiling;
+pub use compiling::compiling_numbered;
 
 mod message_body;
 
@@ -46,6 +52,9 @@ use module_import_cycle_diagnostic::module_import_cycle_diagnostic;
 mod no_location_info_diagnostic;
 use no_location_info_diagnostic::no_location_info_diagnostic;
 
+mod json_diagnostic;
+use json_diagnostic::json_diagnostic;
+
 use super::rest_of_line;
 use super::CompilingModule;
 
@@ -101,7 +110,8 @@ impl GhcMessage {
 }
 
 /// The result of compiling modules in `ghci`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CompilationResult {
     /// All the modules compiled successfully.
     Ok,
@@ -114,7 +124,7 @@ pub enum CompilationResult {
 /// ```text
 /// Foo.hs:81:1: Warning: Defined but not used: `bar'
 /// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 pub struct GhcDiagnostic {
     /// The diagnostic's severity.
     pub severity: Severity,
@@ -124,19 +134,17 @@ pub struct GhcDiagnostic {
     pub span: PositionRange,
     /// The associated message.
     pub message: String,
+    /// The diagnostic code, like `GHC-83865`.
+    pub code: Option<DiagnosticCode>,
+    /// The flags which control this diagnostic, like `-Wunused-imports`.
+    pub flags: Vec<String>,
+    /// Hints and suggested fixes for this diagnostic.
+    pub hints: Vec<String>,
 }
 
 impl Display for GhcDiagnostic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.path {
-            Some(path) => write!(f, "{path}")?,
-            None => write!(f, "<no location info>")?,
-        }
-
-        if !self.span.is_zero() {
-            write!(f, ":{}", self.span)?;
-        }
-        write!(f, ": {}:", self.severity)?;
+        write!(f, "{}: {}:", self.location(), self.severity)?;
 
         // If there's text on the line after the severity (like an error code), put a space before
         // that. If the message starts with a newline, take care to not write trailing whitespace.
@@ -151,6 +159,18 @@ impl Display for GhcDiagnostic {
 }
 
 impl GhcDiagnostic {
+    /// The diagnostic's path and span, like `src/Foo.hs:4:11`, or `<no location info>`.
+    pub fn location(&self) -> String {
+        let mut location = match &self.path {
+            Some(path) => path.to_string(),
+            None => "<no location info>".to_owned(),
+        };
+        if !self.span.is_zero() {
+            location.push_str(&format!(":{}", self.span));
+        }
+        location
+    }
+
     /// Display this diagnostic with GHC-style coloring.
     pub fn display_colored(&self) {
         // Format each part of the warning to match GHC's selective coloring
@@ -230,6 +250,7 @@ fn parse_messages_inner(input: &mut &str) -> PResult<Vec<GhcMessage>> {
     repeat(
         0..,
         alt((
+            json_diagnostic.map(GhcMessage::Diagnostic).map(Item::One),
             compiling.map(GhcMessage::Compiling).map(Item::One),
             generic_diagnostic
                 .map(GhcMessage::Diagnostic)
@@ -326,7 +347,8 @@ mod tests {
                         "  |           ^^^^^^^^^",
                         "",
                     ]
-                    .join("\n")
+                    .join("\n"),
+                    ..Default::default()
                 }),
                 GhcMessage::Summary(CompilationSummary {
                     result: CompilationResult::Err,
@@ -382,6 +404,8 @@ mod tests {
                             "  |           ^^^^^^^^^",
                             ""
                         ].join("\n"),
+                        code: Some(DiagnosticCode::new(83865)),
+                        ..Default::default()
                     },
                 ),
                 GhcMessage::Summary(
@@ -72,6 +72,9 @@ pub fn module_import_cycle_diagnostic(input: &mut &str) -> PResult<Vec<GhcMessag
                 path: Some(path),
                 span: Default::default(),
                 message: message.to_owned(),
+                code: None,
+                flags: vec![],
+                hints: vec![],
             })
         })
         .collect())
@@ -103,19 +106,22 @@ mod tests {
                     severity: Severity::Error,
                     path: Some("./C.hs".into()),
                     span: Default::default(),
-                    message: message.to_owned()
+                    message: message.to_owned(),
+                    ..Default::default()
                 }),
                 GhcMessage::Diagnostic(GhcDiagnostic {
                     severity: Severity::Error,
                     path: Some("A.hs".into()),
                     span: Default::default(),
-                    message: message.to_owned()
+                    message: message.to_owned(),
+                    ..Default::default()
                 }),
                 GhcMessage::Diagnostic(GhcDiagnostic {
                     severity: Severity::Error,
                     path: Some("./B.hs".into()),
                     span: Default::default(),
-                    message: message.to_owned()
+                    message: message.to_owned(),
+                    ..Default::default()
                 }),
             ]
         );
@@ -132,19 +138,22 @@ mod tests {
                     severity: Severity::Error,
                     path: Some("./C.hs".into()),
                     span: Default::default(),
-                    message: message.clone()
+                    message: message.clone(),
+                    ..Default::default()
                 }),
                 GhcMessage::Diagnostic(GhcDiagnostic {
                     severity: Severity::Error,
                     path: Some("A.hs".into()),
                     span: Default::default(),
-                    message: message.clone()
+                    message: message.clone(),
+                    ..Default::default()
                 }),
                 GhcMessage::Diagnostic(GhcDiagnostic {
                     severity: Severity::Error,
                     path: Some("./B.hs".into()),
                     span: Default::default(),
-                    message: message.clone()
+                    message: message.clone(),
+                    ..Default::default()
                 }),
             ]
         );
@@ -162,6 +171,7 @@ mod tests {
                 path: Some("A.hs".into()),
                 span: Default::default(),
                 message: message.into(),
+                ..Default::default()
             })]
         );
 
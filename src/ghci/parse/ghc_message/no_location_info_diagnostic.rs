@@ -3,6 +3,7 @@ use winnow::ascii::space1;
 use winnow::PResult;
 use winnow::Parser;
 
+use crate::ghci::parse::ghc_message::diagnostic_code::parse_message_tags;
 use crate::ghci::parse::ghc_message::message_body::parse_message_body;
 use crate::ghci::parse::ghc_message::position;
 use crate::ghci::parse::ghc_message::severity;
@@ -22,12 +23,16 @@ pub fn no_location_info_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
     let severity = severity::parse_severity_colon.parse_next(input)?;
     let _ = space0.parse_next(input)?;
     let message = parse_message_body.parse_next(input)?;
+    let (code, flags) = parse_message_tags(message);
 
     Ok(GhcDiagnostic {
         severity,
         path: None,
         span: Default::default(),
         message: message.to_owned(),
+        code,
+        flags,
+        hints: vec![],
     })
 }
 
@@ -35,6 +40,7 @@ pub fn no_location_info_diagnostic(input: &mut &str) -> PResult<GhcDiagnostic> {
 mod tests {
     use super::*;
 
+    use crate::ghci::parse::ghc_message::DiagnosticCode;
     use indoc::indoc;
     use pretty_assertions::assert_eq;
     use severity::Severity;
@@ -58,7 +64,8 @@ mod tests {
                 message: "\n    Could not find module ‘Example’\
                     \n    It is not a module in the current program, or in any known package.\
                     \n"
-                .into()
+                .into(),
+                ..Default::default()
             }
         );
 
@@ -83,7 +90,9 @@ mod tests {
                                                                                           src/Foo.hs
                     "
                 )
-                .into()
+                .into(),
+                code: Some(DiagnosticCode::new(29235)),
+                ..Default::default()
             }
         );
 
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use serde::Serialize;
 use winnow::combinator::dispatch;
 use winnow::combinator::empty;
 use winnow::combinator::fail;
@@ -9,11 +10,13 @@ use winnow::PResult;
 use winnow::Parser;
 
 /// The severity of a compiler message.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// Warning-level; non-fatal.
     Warning,
     /// Error-level; fatal.
+    #[default]
     Error,
 }
 
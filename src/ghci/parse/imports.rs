@@ -0,0 +1,71 @@
+use winnow::ascii::space0;
+use winnow::ascii::space1;
+use winnow::combinator::opt;
+use winnow::token::take_till;
+use winnow::PResult;
+use winnow::Parser;
+
+use super::module_name;
+
+/// Parse the names of the modules imported by a Haskell source file.
+///
+/// This only looks at `import` declarations at the start of a line, which is good enough to
+/// approximate the import graph without running the preprocessor or a full parser.
+pub fn parse_imports(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|mut line| import_declaration.parse_next(&mut line).ok())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// An `import` declaration, like `import qualified Data.Map as Map`.
+///
+/// Returns the imported module's name.
+fn import_declaration<'i>(input: &mut &'i str) -> PResult<&'i str> {
+    let _ = ("import", space1).parse_next(input)?;
+    let _ = opt(("{-#", space0, "SOURCE", space0, "#-}", space1)).parse_next(input)?;
+    let _ = opt(("safe", space1)).parse_next(input)?;
+    let _ = opt(("qualified", space1)).parse_next(input)?;
+    // Package-qualified imports, like `import "containers" Data.Map`.
+    let _ = opt(('"', take_till(0.., '"'), '"', space1)).parse_next(input)?;
+    module_name.parse_next(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_imports() {
+        assert_eq!(
+            parse_imports(indoc!(
+                r#"
+                {-# LANGUAGE ImportQualifiedPost #-}
+                module MyLib (someFunc) where
+
+                import Data.Map qualified as Map
+                import qualified Data.Text as T
+                import {-# SOURCE #-} MyLib.Types (Dog(..))
+                import safe "base" Data.List
+                import MyLib.Puppy
+                  ( bark
+                  )
+
+                -- import Commented.Out
+                importantValue = 1
+                "#
+            )),
+            vec![
+                "Data.Map",
+                "Data.Text",
+                "MyLib.Types",
+                "Data.List",
+                "MyLib.Puppy",
+            ]
+        );
+    }
+}
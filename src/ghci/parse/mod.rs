@@ -3,6 +3,7 @@
 mod eval;
 mod ghc_message;
 mod haskell_grammar;
+mod imports;
 mod lines;
 mod module_and_files;
 mod show_modules;
@@ -14,16 +15,21 @@ use lines::rest_of_line;
 use module_and_files::module_and_files;
 
 pub use eval::parse_eval_commands;
+pub use eval::write_eval_results;
 pub use eval::EvalCommand;
+pub use ghc_message::compiling_numbered;
 pub use ghc_message::parse_ghc_messages;
 pub use ghc_message::CompilationResult;
 pub use ghc_message::CompilationSummary;
+#[cfg(test)]
+pub use ghc_message::DiagnosticCode;
 pub use ghc_message::GhcDiagnostic;
 pub use ghc_message::GhcMessage;
 pub use ghc_message::ModulesLoaded;
-#[cfg(test)]
+pub use ghc_message::Position;
 pub use ghc_message::PositionRange;
 pub use ghc_message::Severity;
+pub use imports::parse_imports;
 pub use module_and_files::CompilingModule;
 pub use show_modules::parse_show_modules;
 pub use show_paths::parse_show_paths;
@@ -3,6 +3,7 @@ use std::str::FromStr;
 use camino::Utf8Path;
 use camino::Utf8PathBuf;
 use miette::miette;
+use serde::Serialize;
 use winnow::ascii::space1;
 use winnow::combinator::repeat;
 use winnow::error::AddContext;
@@ -31,7 +32,7 @@ use super::module_name;
 /// ```text
 /// Compiling A.Puppy.Doggy ( src/A/Puppy/Doggy.hs, dist-newstyle/A/Puppy/Doggy.o, interpreted ) [Doggy.Lint package changed]
 /// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct CompilingModule {
     /// The module's fully-qualified name.
     pub name: String,
@@ -11,8 +11,14 @@ use nix::unistd::Pid;
 use tokio::sync::mpsc;
 use tracing::instrument;
 
+use crate::hooks::HookContext;
+use crate::hooks::HookOpts;
+use crate::hooks::LifecycleEvent;
+use crate::maybe_async_command::CommandHandles;
 use crate::shutdown::ShutdownHandle;
 
+use super::EventStream;
+
 pub struct GhciProcess {
     pub shutdown: ShutdownHandle,
     pub process_group_id: Pid,
@@ -20,6 +26,13 @@ pub struct GhciProcess {
     /// This is used for the graceful shutdown implementation and for routine `ghci` session
     /// restarts.
     pub restart_receiver: mpsc::Receiver<()>,
+    /// Hooks to run if `ghci` crashes.
+    pub hooks: HookOpts,
+    /// Where to emit events about hooks being run.
+    pub event_stream: EventStream,
+    /// If given, notify the `ghci` manager that `ghci` crashed so that it can restart the session,
+    /// rather than shutting down.
+    pub crash_sender: Option<mpsc::Sender<Pid>>,
 }
 
 impl GhciProcess {
@@ -38,7 +51,15 @@ impl GhciProcess {
             }
             result = &mut wait => {
                 self.exited(result.into_diagnostic()?).await;
-                let _ = self.shutdown.request_shutdown();
+                self.crashed().await;
+                match &self.crash_sender {
+                    Some(sender) => {
+                        let _ = sender.try_send(self.process_group_id);
+                    }
+                    None => {
+                        let _ = self.shutdown.request_shutdown();
+                    }
+                }
             }
         }
         Ok(())
@@ -71,4 +92,22 @@ impl GhciProcess {
     async fn exited(&self, status: ExitStatus) {
         tracing::debug!("ghci exited: {status}");
     }
+
+    /// Run the crash hooks after `ghci` exits unexpectedly.
+    async fn crashed(&self) {
+        // Nothing waits on asynchronous crash hooks; they finish on their own.
+        let mut handles = CommandHandles::default();
+        if let Err(err) = self
+            .hooks
+            .run_shell_hooks(
+                LifecycleEvent::Crash,
+                &mut handles,
+                &self.event_stream,
+                &HookContext::default(),
+            )
+            .await
+        {
+            tracing::error!("Failed to run crash hooks: {err:?}");
+        }
+    }
 }
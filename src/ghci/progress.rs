@@ -0,0 +1,227 @@
+//! Live progress for compiling modules, parsed from `[n of m] Compiling` lines as `ghci` prints
+//! them.
+
+use std::fmt::Display;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::sync::watch;
+use winnow::Parser;
+
+use super::parse::compiling_numbered;
+use super::parse::CompilingModule;
+
+/// ANSI escapes to move to the start of the line and clear it.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Progress compiling modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilationProgress {
+    /// The number of modules which have finished compiling.
+    pub compiled: usize,
+    /// The total number of modules to compile.
+    pub total: usize,
+    /// The name of the module currently being compiled.
+    pub module: String,
+    /// When we saw the first module being compiled.
+    pub start: Instant,
+}
+
+impl CompilationProgress {
+    /// The fraction of modules which have finished compiling, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.compiled as f64 / self.total as f64).min(1.0)
+        }
+    }
+
+    /// Estimate how much longer compilation will take, given how long it's taken so far.
+    ///
+    /// This assumes each remaining module will take as long as the average module so far.
+    pub fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        if self.compiled == 0 {
+            return None;
+        }
+        let remaining_modules = self.total.saturating_sub(self.compiled) as u32;
+        Some(elapsed / self.compiled as u32 * remaining_modules)
+    }
+}
+
+impl Display for CompilationProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elapsed = self.start.elapsed();
+        write!(
+            f,
+            "[{}/{}] Compiling {} ({} elapsed",
+            self.compiled,
+            self.total,
+            self.module,
+            format_seconds(elapsed)
+        )?;
+        if let Some(remaining) = self.remaining(elapsed) {
+            write!(f, ", ~{} remaining", format_seconds(remaining))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Parse a `[n of m] Compiling` line, without its trailing newline.
+pub(crate) fn compiling_line(line: &str) -> Option<(usize, usize, CompilingModule)> {
+    if !line.starts_with('[') {
+        return None;
+    }
+    compiling_numbered.parse(&format!("{line}\n")).ok()
+}
+
+/// Format a duration to the nearest second, like `1m 5s`.
+fn format_seconds(duration: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(duration.as_secs())).to_string()
+}
+
+/// Tracks compilation progress and shares it with the TUI, or shows it as a single updating
+/// status line on the terminal.
+#[derive(Debug, Clone)]
+pub struct ProgressHandle {
+    sender: Arc<watch::Sender<Option<CompilationProgress>>>,
+    /// If given, `[n of m] Compiling` lines are replaced with a status line, and this is `true`
+    /// while the status line is the last thing on the terminal.
+    status_line: Option<Arc<AtomicBool>>,
+}
+
+impl ProgressHandle {
+    /// Create a new progress handle. If `status_line` is true, progress is shown as a single
+    /// updating line in `ghci`'s output.
+    pub fn new(status_line: bool) -> Self {
+        let (sender, _receiver) = watch::channel(None);
+        Self {
+            sender: Arc::new(sender),
+            status_line: status_line.then(|| Arc::new(AtomicBool::new(false))),
+        }
+    }
+
+    /// Get a receiver for progress updates. The value is `None` when nothing is compiling.
+    pub fn subscribe(&self) -> watch::Receiver<Option<CompilationProgress>> {
+        self.sender.subscribe()
+    }
+
+    /// Observe a line of `ghci` output, updating the progress if it's a `[n of m] Compiling`
+    /// line.
+    ///
+    /// Returns the text to write instead of the line and its newline, if any.
+    pub fn line(&self, line: &str) -> Option<String> {
+        let progress = compiling_line(line).map(|(index, total, module)| {
+            let start = self
+                .sender
+                .borrow()
+                .as_ref()
+                .map(|progress| progress.start)
+                .unwrap_or_else(Instant::now);
+            CompilationProgress {
+                compiled: index.saturating_sub(1),
+                total,
+                module: module.name,
+                start,
+            }
+        });
+
+        let status_line = self.status_line.as_ref();
+        match progress {
+            Some(progress) => {
+                let replacement = status_line.map(|visible| {
+                    visible.store(true, Ordering::SeqCst);
+                    format!("{CLEAR_LINE}{progress}")
+                });
+                self.sender.send_replace(Some(progress));
+                replacement
+            }
+            None => self
+                .clear_status_line()
+                .map(|clear| format!("{clear}{line}\n")),
+        }
+    }
+
+    /// If the status line is showing, get the text to clear it with.
+    ///
+    /// Other output written to the terminal should clear the status line first.
+    pub fn clear_status_line(&self) -> Option<&'static str> {
+        self.status_line
+            .as_ref()
+            .filter(|visible| visible.swap(false, Ordering::SeqCst))
+            .map(|_| CLEAR_LINE)
+    }
+
+    /// Note that compilation has finished.
+    pub fn finish(&self) {
+        self.sender.send_replace(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_remaining() {
+        let progress = CompilationProgress {
+            compiled: 10,
+            total: 40,
+            module: "MyLib".to_owned(),
+            start: Instant::now(),
+        };
+        assert_eq!(progress.ratio(), 0.25);
+        assert_eq!(
+            progress.remaining(Duration::from_secs(5)),
+            Some(Duration::from_secs(15))
+        );
+
+        let progress = CompilationProgress {
+            compiled: 0,
+            ..progress
+        };
+        assert_eq!(progress.remaining(Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_progress_lines() {
+        let handle = ProgressHandle::new(false);
+        let receiver = handle.subscribe();
+
+        assert_eq!(
+            handle.line("[2 of 4] Compiling MyLib ( src/MyLib.hs, interpreted )"),
+            None
+        );
+        let progress = receiver.borrow().clone().unwrap();
+        assert_eq!(progress.compiled, 1);
+        assert_eq!(progress.total, 4);
+        assert_eq!(progress.module, "MyLib");
+
+        assert_eq!(handle.line("Ok, four modules loaded."), None);
+        handle.finish();
+        assert_eq!(*receiver.borrow(), None);
+    }
+
+    #[test]
+    fn test_status_line() {
+        let handle = ProgressHandle::new(true);
+
+        let status = handle
+            .line("[1 of 4] Compiling MyLib ( src/MyLib.hs, interpreted )")
+            .unwrap();
+        assert!(status.starts_with("\r\x1b[2K[0/4] Compiling MyLib (0s elapsed"));
+        assert!(!status.ends_with('\n'));
+
+        // The next line clears the status line.
+        assert_eq!(
+            handle.line("Ok, four modules loaded."),
+            Some("\r\x1b[2KOk, four modules loaded.\n".to_owned())
+        );
+        assert_eq!(handle.line("Ok, four modules loaded."), None);
+        assert_eq!(handle.clear_status_line(), None);
+    }
+}
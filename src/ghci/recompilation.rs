@@ -0,0 +1,168 @@
+//! Explaining why modules were recompiled during a reload.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::normal_path::NormalPath;
+
+/// A module which was recompiled, along with the modules it imports.
+#[derive(Debug, Clone)]
+pub struct RecompiledModule {
+    /// The module's fully-qualified name.
+    pub name: String,
+    /// The path to the module's source file.
+    pub path: NormalPath,
+    /// The names of the modules it imports.
+    pub imports: Vec<String>,
+}
+
+/// Explain why each recompiled module was recompiled, as a tree from each directly changed file
+/// to the recompiled modules which depend on it.
+///
+/// Each module is only shown once, under the first changed file it was reached from (through the
+/// shortest chain of imports). `modules` should be in the order they were compiled in.
+///
+/// Returns `None` if no modules were recompiled.
+pub fn explain_recompilation(
+    changed_files: &BTreeSet<NormalPath>,
+    modules: &[RecompiledModule],
+) -> Option<String> {
+    if modules.is_empty() {
+        return None;
+    }
+
+    let recompiled = modules
+        .iter()
+        .map(|module| module.name.as_str())
+        .collect::<HashSet<_>>();
+
+    // Map from each recompiled module to the recompiled modules which import it.
+    let mut dependents = HashMap::<&str, Vec<&str>>::new();
+    for module in modules {
+        for import in &module.imports {
+            if let Some(import) = recompiled.get(import.as_str()) {
+                dependents.entry(import).or_default().push(&module.name);
+            }
+        }
+    }
+
+    // Walk breadth-first from the changed modules so each module is placed under its closest
+    // changed ancestor.
+    let mut seen = HashSet::new();
+    let mut children = HashMap::<&str, Vec<&str>>::new();
+    let roots = modules
+        .iter()
+        .filter(|module| changed_files.contains(&module.path))
+        .collect::<Vec<_>>();
+    for root in &roots {
+        seen.insert(root.name.as_str());
+    }
+    let mut queue = roots
+        .iter()
+        .map(|root| root.name.as_str())
+        .collect::<VecDeque<_>>();
+    while let Some(name) = queue.pop_front() {
+        for &dependent in dependents.get(name).into_iter().flatten() {
+            if seen.insert(dependent) {
+                children.entry(name).or_default().push(dependent);
+                queue.push_back(dependent);
+            }
+        }
+    }
+
+    let mut explanation = String::from("Recompiled modules:");
+    for root in &roots {
+        write!(explanation, "\n{} ({})", root.path, root.name)
+            .expect("Writing to a `String` never fails");
+        write_children(&mut explanation, &children, &root.name, "");
+    }
+
+    let other = modules
+        .iter()
+        .filter(|module| !seen.contains(module.name.as_str()))
+        .map(|module| module.name.as_str())
+        .collect::<Vec<_>>();
+    if !other.is_empty() {
+        write!(
+            explanation,
+            "\nNot caused by a changed file: {}",
+            other.join(", ")
+        )
+        .expect("Writing to a `String` never fails");
+    }
+
+    Some(explanation)
+}
+
+fn write_children(
+    explanation: &mut String,
+    children: &HashMap<&str, Vec<&str>>,
+    name: &str,
+    prefix: &str,
+) {
+    let names = match children.get(name) {
+        Some(names) => names,
+        None => return,
+    };
+    for (i, child) in names.iter().enumerate() {
+        let last = i == names.len() - 1;
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        write!(explanation, "\n{prefix}{branch}{child}")
+            .expect("Writing to a `String` never fails");
+        write_children(explanation, children, child, &format!("{prefix}{indent}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+
+    fn module(name: &str, imports: &[&str]) -> RecompiledModule {
+        RecompiledModule {
+            name: name.to_owned(),
+            path: NormalPath::new(format!("src/{}.hs", name.replace('.', "/")), "/").unwrap(),
+            imports: imports.iter().map(|&import| import.to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_explain_recompilation() {
+        let modules = [
+            module("MyLib.Types", &["Data.Text"]),
+            module("MyLib.Dog", &["MyLib.Types"]),
+            module("MyLib.Cat", &["MyLib.Types"]),
+            module("MyLib", &["MyLib.Dog", "MyLib.Cat"]),
+            module("Main", &["MyLib", "MyLib.Types"]),
+            module("Paths_mylib", &[]),
+        ];
+        let changed_files = [NormalPath::new("src/MyLib/Types.hs", "/").unwrap()]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            explain_recompilation(&changed_files, &modules).unwrap(),
+            indoc!(
+                "
+                Recompiled modules:
+                src/MyLib/Types.hs (MyLib.Types)
+                ├── MyLib.Dog
+                │   └── MyLib
+                ├── MyLib.Cat
+                └── Main
+                Not caused by a changed file: Paths_mylib"
+            )
+        );
+
+        assert_eq!(explain_recompilation(&changed_files, &[]), None);
+    }
+}
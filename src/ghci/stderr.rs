@@ -15,6 +15,7 @@ use tracing::instrument;
 
 use crate::shutdown::ShutdownHandle;
 
+use super::progress::ProgressHandle;
 use super::writer::GhciWriter;
 
 /// An event sent to a `ghci` session's stderr channel.
@@ -34,6 +35,8 @@ pub struct GhciStderr {
     pub receiver: mpsc::Receiver<StderrEvent>,
     /// Output buffer.
     pub buffer: String,
+    /// Compilation progress; if it's shown as a status line, it's cleared before writing.
+    pub progress: Option<ProgressHandle>,
 }
 
 impl GhciStderr {
@@ -98,6 +101,16 @@ impl GhciStderr {
         tracing::debug!(line, "Read stderr line");
         line.push('\n');
         self.buffer.push_str(&line);
+        if let Some(clear) = self
+            .progress
+            .as_ref()
+            .and_then(|progress| progress.clear_status_line())
+        {
+            self.writer
+                .write_all(clear.as_bytes())
+                .await
+                .into_diagnostic()?;
+        }
         self.writer
             .write_all(line.as_bytes())
             .await
@@ -130,6 +143,7 @@ impl GhciStderr {
                 }
                 None => {
                     tracing::debug!("No more lines available from stderr");
+                    break;
                 }
             }
         }
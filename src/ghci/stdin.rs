@@ -1,6 +1,12 @@
+use std::time::Duration;
+use std::time::Instant;
+
 use itertools::Itertools;
 use miette::Context;
 use miette::IntoDiagnostic;
+use nix::sys::signal;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use tokio::io::AsyncWriteExt;
 use tokio::process::ChildStdin;
 use tracing::instrument;
@@ -18,10 +24,17 @@ use crate::ghci::GhciStdout;
 pub struct GhciStdin {
     /// Inner stdin writer.
     pub stdin: ChildStdin,
+    /// The `ghci` session's process group ID, used to interrupt it.
+    pub process_group_id: Pid,
+    /// The module whose top-level scope was added for an eval command, if any.
+    ///
+    /// If the eval is cancelled, the scope is removed when `ghci` is interrupted.
+    pub eval_module: Option<String>,
 }
 
 impl GhciStdin {
-    /// Write a line on `stdin` and wait for a prompt on stdout.
+    /// Write a line on `stdin` and wait for a prompt on stdout, returning the output before the
+    /// prompt.
     ///
     /// The `line` should contain the trailing newline.
     ///
@@ -33,7 +46,7 @@ impl GhciStdin {
         line: &str,
         find: FindAt,
         log: &mut CompilationLog,
-    ) -> miette::Result<()> {
+    ) -> miette::Result<String> {
         match self.stdin.write_all(line.as_bytes()).await {
             Ok(_) => stdout.prompt(find, log).await,
             Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Err(miette::miette!(
@@ -55,10 +68,11 @@ impl GhciStdin {
         log: &mut CompilationLog,
     ) -> miette::Result<()> {
         self.write_line_with_prompt_at(stdout, line, FindAt::LineStart, log)
-            .await
+            .await?;
+        Ok(())
     }
 
-    /// Run a [`GhciCommand`].
+    /// Run a [`GhciCommand`], returning its output.
     ///
     /// The command may be multiple lines.
     #[instrument(skip(self, stdout), level = "debug")]
@@ -67,11 +81,75 @@ impl GhciStdin {
         stdout: &mut GhciStdout,
         command: &GhciCommand,
         log: &mut CompilationLog,
-    ) -> miette::Result<()> {
+    ) -> miette::Result<String> {
+        let mut output = String::new();
         for line in command.lines() {
-            self.write_line(stdout, &format!("{line}\n"), log).await?;
+            output.push_str(
+                &self
+                    .write_line_with_prompt_at(stdout, &format!("{line}\n"), FindAt::LineStart, log)
+                    .await?,
+            );
+        }
+
+        Ok(output)
+    }
+
+    /// Run a [`GhciCommand`], returning its output, or `None` if it ran for longer than
+    /// `timeout` and was interrupted.
+    #[instrument(skip(self, stdout), level = "debug")]
+    pub async fn run_command_with_timeout(
+        &mut self,
+        stdout: &mut GhciStdout,
+        command: &GhciCommand,
+        timeout: Option<Duration>,
+        log: &mut CompilationLog,
+    ) -> miette::Result<Option<String>> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return self.run_command(stdout, command, log).await.map(Some),
+        };
+        match tokio::time::timeout(timeout, self.run_command(stdout, command, log)).await {
+            Ok(output) => output.map(Some),
+            Err(_) => {
+                tracing::error!(
+                    "Command timed out after {}: {command}",
+                    humantime::format_duration(timeout)
+                );
+                self.send_sigint(stdout).await?;
+                Ok(None)
+            }
         }
+    }
 
+    /// Interrupt `ghci` with `SIGINT` and wait for it to print a prompt.
+    #[instrument(skip_all, level = "debug")]
+    pub async fn send_sigint(&mut self, stdout: &mut GhciStdout) -> miette::Result<()> {
+        let start_instant = Instant::now();
+        stdout.compile_times.interrupt();
+        signal::killpg(self.process_group_id, Signal::SIGINT)
+            .into_diagnostic()
+            .wrap_err("Failed to send `Ctrl-C` (`SIGINT`) to ghci session")?;
+        stdout
+            .prompt(
+                FindAt::Anywhere,
+                // Ignore compilation messages.
+                &mut Default::default(),
+            )
+            .await?;
+        tracing::debug!("Interrupted ghci in {:.2?}", start_instant.elapsed());
+        self.end_eval_scope(stdout, &mut Default::default()).await
+    }
+
+    /// Remove the module scope added by [`GhciStdin::eval`], if it's still there.
+    async fn end_eval_scope(
+        &mut self,
+        stdout: &mut GhciStdout,
+        log: &mut CompilationLog,
+    ) -> miette::Result<()> {
+        if let Some(module_name) = self.eval_module.take() {
+            self.write_line(stdout, &format!(":module - *{module_name}\n"), log)
+                .await?;
+        }
         Ok(())
     }
 
@@ -148,23 +226,30 @@ impl GhciStdin {
             .await
     }
 
+    /// Evaluate a command in the top-level scope of a module, returning its output, or `None` if
+    /// it ran for longer than `timeout` and was interrupted.
     #[instrument(skip(self, stdout), level = "debug")]
     pub async fn eval(
         &mut self,
         stdout: &mut GhciStdout,
         module_name: &str,
         command: &GhciCommand,
+        timeout: Option<Duration>,
         log: &mut CompilationLog,
-    ) -> miette::Result<()> {
+    ) -> miette::Result<Option<String>> {
+        // Set this first, so that the scope is removed even if we're cancelled partway through
+        // adding it.
+        self.eval_module = Some(module_name.to_owned());
         self.write_line(stdout, &format!(":module + *{module_name}\n"), log)
             .await?;
 
-        self.run_command(stdout, command, log).await?;
-
-        self.write_line(stdout, &format!(":module - *{module_name}\n"), log)
+        let output = self
+            .run_command_with_timeout(stdout, command, timeout, log)
             .await?;
 
-        Ok(())
+        self.end_eval_scope(stdout, log).await?;
+
+        Ok(output)
     }
 
     #[instrument(skip(self, stdout), level = "debug")]
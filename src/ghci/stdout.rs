@@ -12,11 +12,13 @@ use crate::incremental_reader::IncrementalReader;
 use crate::incremental_reader::ReadOpts;
 use crate::incremental_reader::WriteBehavior;
 
+use super::compile_times::CompileTimes;
 use super::parse::parse_ghc_messages;
 use super::parse::parse_show_modules;
 use super::parse::parse_show_paths;
 use super::parse::parse_show_targets;
 use super::parse::ShowPaths;
+use super::progress::ProgressHandle;
 use super::stderr::StderrEvent;
 use super::writer::GhciWriter;
 use super::CompilationLog;
@@ -32,11 +34,18 @@ pub struct GhciStdout {
     pub prompt_patterns: AhoCorasick,
     /// A buffer to read data into. Lets us avoid allocating buffers in the [`IncrementalReader`].
     pub buffer: Vec<u8>,
+    /// Progress for compiling modules, reset when a prompt is seen.
+    pub progress: Option<ProgressHandle>,
+    /// Compile times for each module, collected into the log when a prompt is seen.
+    pub compile_times: CompileTimes,
 }
 
 impl GhciStdout {
+    /// Parse compiler messages from `data` and the buffered `stderr` output into the log.
+    ///
+    /// Returns the buffered `stderr` output.
     #[instrument(skip_all, level = "debug")]
-    async fn parse_into_log(&self, data: &str, log: &mut CompilationLog) -> miette::Result<()> {
+    async fn parse_into_log(&self, data: &str, log: &mut CompilationLog) -> miette::Result<String> {
         // Parse GHCi output into compiler messages.
         //
         // These include diagnostics, which modules were compiled, and a compilation summary.
@@ -50,7 +59,7 @@ impl GhciStdout {
         };
         log.extend(parse_ghc_messages(data).wrap_err("Failed to parse compiler output")?);
         log.extend(parse_ghc_messages(&stderr_data).wrap_err("Failed to parse compiler output")?);
-        Ok(())
+        Ok(stderr_data)
     }
 
     #[instrument(skip_all, name = "stdout_initialize", level = "debug")]
@@ -77,8 +86,15 @@ impl GhciStdout {
         Ok(())
     }
 
+    /// Wait for a prompt, parsing compiler messages into the log.
+    ///
+    /// Returns the output before the prompt, from `stdout` followed by `stderr`.
     #[instrument(skip_all, level = "debug")]
-    pub async fn prompt(&mut self, find: FindAt, log: &mut CompilationLog) -> miette::Result<()> {
+    pub async fn prompt(
+        &mut self,
+        find: FindAt,
+        log: &mut CompilationLog,
+    ) -> miette::Result<String> {
         self.stderr_sender
             .send(StderrEvent::ClearBuffer)
             .await
@@ -94,9 +110,13 @@ impl GhciStdout {
             })
             .await?;
         tracing::debug!(bytes = data.len(), "Got data from ghci");
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        log.module_times.extend(self.compile_times.finish());
 
-        self.parse_into_log(&data, log).await?;
-        Ok(())
+        let stderr_data = self.parse_into_log(&data, log).await?;
+        Ok(data + &stderr_data)
     }
 
     #[instrument(skip_all, level = "debug")]
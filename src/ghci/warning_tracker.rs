@@ -49,6 +49,11 @@ impl WarningTracker {
         self.current_changed_files.insert(path);
     }
 
+    /// Get the files that were directly changed in the current reload operation.
+    pub fn changed_files(&self) -> &BTreeSet<NormalPath> {
+        &self.current_changed_files
+    }
+
     /// Update warnings from a compilation log.
     ///
     /// This method implements smart warning persistence logic:
@@ -123,6 +128,19 @@ impl WarningTracker {
         self.warnings.values().map(|w| w.len()).sum()
     }
 
+    /// Get the number of tracked warnings for each warning flag, like `-Wunused-imports`.
+    ///
+    /// Warnings without a flag aren't counted.
+    pub fn warning_count_by_flag(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for warning in self.warnings.values().flatten() {
+            for flag in &warning.flags {
+                *counts.entry(flag.as_str()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
     /// Check if there are any warnings.
     #[allow(dead_code)]
     pub fn has_warnings(&self) -> bool {
@@ -149,6 +167,7 @@ mod tests {
             path: Some(path.into()),
             span: PositionRange::new(1, 1, 1, 1),
             message: message.to_string(),
+            ..Default::default()
         }
     }
 
@@ -160,6 +179,7 @@ mod tests {
             diagnostics,
             compiled_modules,
             summary: None,
+            module_times: vec![],
         }
     }
 
@@ -207,6 +227,36 @@ mod tests {
         assert!(tracker.warnings.contains_key(&path2));
     }
 
+    #[test]
+    fn test_warning_count_by_flag() {
+        let mut tracker = WarningTracker::new();
+        let base_dir = std::env::current_dir().unwrap();
+        let path1 = NormalPath::new("src/test1.hs", &base_dir).unwrap();
+        let path2 = NormalPath::new("src/test2.hs", &base_dir).unwrap();
+
+        let with_flag = |path: &str, flag: &str| GhcDiagnostic {
+            flags: vec![flag.to_owned()],
+            ..create_test_diagnostic(Severity::Warning, path, "warning")
+        };
+
+        tracker.warnings.insert(
+            path1,
+            vec![
+                with_flag("src/test1.hs", "-Wunused-imports"),
+                with_flag("src/test1.hs", "-Wunused-top-binds"),
+                create_test_diagnostic(Severity::Warning, "src/test1.hs", "no flag"),
+            ],
+        );
+        tracker
+            .warnings
+            .insert(path2, vec![with_flag("src/test2.hs", "-Wunused-imports")]);
+
+        assert_eq!(
+            tracker.warning_count_by_flag(),
+            BTreeMap::from([("-Wunused-imports", 2), ("-Wunused-top-binds", 1)])
+        );
+    }
+
     #[test]
     fn test_update_warnings_from_log_direct_change() {
         let mut tracker = WarningTracker::new();
@@ -274,6 +324,7 @@ mod tests {
             path: None,
             span: PositionRange::new(1, 1, 1, 1),
             message: "warning without path".to_string(),
+            ..Default::default()
         };
 
         let log = create_test_compilation_log(vec![diagnostic_no_path], vec![]);
@@ -0,0 +1,159 @@
+//! Tracking when shell hooks run (or when `ghciwatch` writes eval results to source files), so
+//! that filesystem events caused by their writes can be ignored.
+//!
+//! Each window is limited to the paths the hook is expected to write, so that other changes made
+//! while a hook is running still trigger reloads.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::ignore::GlobMatcher;
+
+/// How long after a hook finishes to keep attributing filesystem events to it.
+///
+/// Filesystem notifications are delivered asynchronously, so events for writes made near the end
+/// of a hook may be timestamped shortly after it exits.
+const GRACE_PERIOD: Duration = Duration::from_millis(250);
+
+/// How long to remember finished windows for.
+///
+/// Events are debounced before they reach the watcher's event handler, so this needs to be longer
+/// than any reasonable debounce duration.
+const RETAIN_PERIOD: Duration = Duration::from_secs(60);
+
+/// The time windows during which shell hooks were running.
+///
+/// Cloning a [`HookWindows`] gives another handle to the same windows, so one can be held by the
+/// hooks and another by the file watcher.
+#[derive(Debug, Clone, Default)]
+pub struct HookWindows(Arc<Mutex<Vec<Window>>>);
+
+/// The paths a window's events are ignored for.
+#[derive(Debug, Clone)]
+pub enum WindowPaths {
+    /// Paths matching the given globs, like those given to `--ignore-hook-writes`.
+    Matching(Arc<GlobMatcher>),
+    /// Particular files, like a source file `ghciwatch` is writing eval results to.
+    Files(Vec<PathBuf>),
+}
+
+impl WindowPaths {
+    fn contains(&self, path: &Path) -> bool {
+        match self {
+            WindowPaths::Matching(globs) => globs.matched(path).is_whitelist(),
+            WindowPaths::Files(files) => files.iter().any(|file| file == path),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Window {
+    start: Instant,
+    /// `None` while the hook is still running.
+    end: Option<Instant>,
+    paths: WindowPaths,
+}
+
+impl Window {
+    fn contains(&self, time: Instant, path: &Path) -> bool {
+        self.start <= time
+            && match self.end {
+                Some(end) => time <= end + GRACE_PERIOD,
+                None => true,
+            }
+            && self.paths.contains(path)
+    }
+}
+
+impl HookWindows {
+    /// Start a window for a hook which writes the given paths. The window ends when the returned
+    /// guard is dropped.
+    pub fn start(&self, paths: WindowPaths) -> HookWindow {
+        let start = Instant::now();
+        let mut windows = self.0.lock().expect("Hook windows mutex was poisoned");
+        windows.retain(|window| match window.end {
+            Some(end) => start.saturating_duration_since(end) < RETAIN_PERIOD,
+            None => true,
+        });
+        windows.push(Window {
+            start,
+            end: None,
+            paths,
+        });
+        HookWindow {
+            windows: self.clone(),
+            start,
+        }
+    }
+
+    /// Was a hook which writes the given path running at the given time?
+    pub fn contains(&self, time: Instant, path: &Path) -> bool {
+        self.0
+            .lock()
+            .expect("Hook windows mutex was poisoned")
+            .iter()
+            .any(|window| window.contains(time, path))
+    }
+}
+
+/// A guard for a running hook's window. When this is dropped, the window ends.
+#[derive(Debug)]
+pub struct HookWindow {
+    windows: HookWindows,
+    start: Instant,
+}
+
+impl Drop for HookWindow {
+    fn drop(&mut self) {
+        let end = Instant::now();
+        if let Ok(mut windows) = self.windows.0.lock() {
+            if let Some(window) = windows
+                .iter_mut()
+                .find(|window| window.start == self.start && window.end.is_none())
+            {
+                window.end = Some(end);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_windows() {
+        let path = Path::new("package.yaml");
+        let windows = HookWindows::default();
+        let before = Instant::now();
+        assert!(!windows.contains(before, path));
+
+        std::thread::sleep(Duration::from_millis(1));
+        let window = windows.start(WindowPaths::Files(vec![path.to_owned()]));
+        assert!(windows.contains(Instant::now(), path));
+        assert!(!windows.contains(before, path));
+        // Changes to other paths aren't attributed to the hook.
+        assert!(!windows.contains(Instant::now(), Path::new("src/MyLib.hs")));
+
+        drop(window);
+        // Events shortly after the hook finishes are still attributed to it.
+        assert!(windows.contains(Instant::now(), path));
+        assert!(!windows.contains(Instant::now() + RETAIN_PERIOD, path));
+    }
+
+    #[test]
+    fn test_hook_windows_globs() {
+        let globs = GlobMatcher::from_globs(["*.cabal", "!vendored.cabal"]).unwrap();
+        let cwd = crate::current_dir().unwrap();
+        let windows = HookWindows::default();
+        let _window = windows.start(WindowPaths::Matching(Arc::new(globs)));
+        let now = Instant::now();
+        assert!(windows.contains(now, &cwd.join("my-lib.cabal")));
+        assert!(!windows.contains(now, &cwd.join("vendored.cabal")));
+        assert!(!windows.contains(now, &cwd.join("src/MyLib.hs")));
+    }
+}
@@ -2,19 +2,37 @@
 
 use std::fmt::Display;
 use std::fmt::Write;
-use std::process::ExitStatus;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
-use clap::builder::ValueParserFactory;
+use camino::Utf8PathBuf;
 use clap::Arg;
 use clap::ArgAction;
 use clap::Args;
 use clap::FromArgMatches;
 use enum_iterator::Sequence;
 use indoc::indoc;
-use tokio::task::JoinHandle;
-
+use miette::miette;
+use winnow::combinator::cut_err;
+use winnow::combinator::opt;
+use winnow::combinator::preceded;
+use winnow::combinator::rest;
+use winnow::combinator::terminated;
+use winnow::token::take_until;
+use winnow::PResult;
+use winnow::Parser;
+
+use crate::ghci::parse::CompilationResult;
+use crate::ghci::Event;
+use crate::ghci::EventStream;
 use crate::ghci::GhciCommand;
+use crate::hook_windows::HookWindow;
+use crate::hook_windows::HookWindows;
+use crate::hook_windows::WindowPaths;
+use crate::ignore::GlobMatcher;
+use crate::maybe_async_command::CommandHandles;
 use crate::maybe_async_command::MaybeAsyncCommand;
 
 /// A lifecycle event that triggers hooks.
@@ -28,6 +46,14 @@ pub enum LifecycleEvent {
     Reload(When),
     /// When a `ghci` session is restarted (when a module is removed or renamed).
     Restart(When),
+    /// When eval commands are run (after startup, after reloads).
+    Eval(When),
+    /// When compilation fails after previously succeeding.
+    FirstError,
+    /// When the `ghci` process exits unexpectedly.
+    Crash,
+    /// When `ghciwatch` is shutting down.
+    Shutdown,
 }
 
 impl Display for LifecycleEvent {
@@ -35,7 +61,11 @@ impl Display for LifecycleEvent {
         if let Some(when) = self.when() {
             write!(f, "{}-", when)?;
         }
-        write!(f, "{}", self.event_name())
+        write!(f, "{}", self.event_name())?;
+        if let Some(outcome) = self.when().and_then(|when| when.outcome()) {
+            write!(f, "-{}", outcome)?;
+        }
+        Ok(())
     }
 }
 
@@ -47,6 +77,10 @@ impl LifecycleEvent {
             LifecycleEvent::Startup(_) => "startup",
             LifecycleEvent::Reload(_) => "reload",
             LifecycleEvent::Restart(_) => "restart",
+            LifecycleEvent::Eval(_) => "eval",
+            LifecycleEvent::FirstError => "first-error",
+            LifecycleEvent::Crash => "crash",
+            LifecycleEvent::Shutdown => "shutdown",
         }
     }
 
@@ -57,6 +91,10 @@ impl LifecycleEvent {
             LifecycleEvent::Startup(_) => "starting up",
             LifecycleEvent::Reload(_) => "reloading",
             LifecycleEvent::Restart(_) => "restarting",
+            LifecycleEvent::Eval(_) => "evaluating",
+            LifecycleEvent::FirstError => "failing",
+            LifecycleEvent::Crash => "crashing",
+            LifecycleEvent::Shutdown => "shutting down",
         }
     }
 
@@ -82,12 +120,37 @@ impl LifecycleEvent {
                 The GHCi session must be restarted when `.cabal` or `.ghci` files are modified.
                 "
             ),
+            LifecycleEvent::Eval(_) => indoc!(
+                "
+                Eval hooks are run around eval commands, if any are found (see `--enable-eval`).
+                "
+            ),
+            LifecycleEvent::FirstError => indoc!(
+                "
+                First-error hooks are run when compilation fails after previously succeeding,
+                but not again until compilation succeeds.
+                "
+            ),
+            LifecycleEvent::Crash => indoc!(
+                "
+                Crash hooks are run when the GHCi process exits unexpectedly.
+                "
+            ),
+            LifecycleEvent::Shutdown => indoc!(
+                "
+                Shutdown hooks are run when `ghciwatch` is shutting down.
+                "
+            ),
         }.trim_end_matches('\n')
     }
 
     fn get_help_name(&self) -> Option<&'static str> {
         match self {
             LifecycleEvent::Test => Some("tests"),
+            LifecycleEvent::Eval(_) => Some("eval commands"),
+            LifecycleEvent::FirstError => Some("when compilation starts failing"),
+            LifecycleEvent::Crash => Some("when `ghci` crashes"),
+            LifecycleEvent::Shutdown => Some("when `ghciwatch` shuts down"),
             _ => None,
         }
     }
@@ -98,16 +161,55 @@ impl LifecycleEvent {
             LifecycleEvent::Startup(when) => Some(*when),
             LifecycleEvent::Reload(when) => Some(*when),
             LifecycleEvent::Restart(when) => Some(*when),
+            LifecycleEvent::Eval(when) => Some(*when),
+            LifecycleEvent::FirstError | LifecycleEvent::Crash | LifecycleEvent::Shutdown => None,
         }
     }
 
+    /// Get the event to run conditionally on the result of compilation after this event, if any.
+    ///
+    /// For example, `after-reload` gives `after-reload-success` if compilation succeeded and
+    /// `after-reload-failure` if it failed.
+    pub fn with_outcome(&self, result: Option<CompilationResult>) -> Option<Self> {
+        let when = match (self.when()?, result) {
+            (When::After, Some(CompilationResult::Err)) => When::AfterFailure,
+            (When::After, _) => When::AfterSuccess,
+            _ => {
+                return None;
+            }
+        };
+        match self {
+            LifecycleEvent::Startup(_) => Some(LifecycleEvent::Startup(when)),
+            LifecycleEvent::Reload(_) => Some(LifecycleEvent::Reload(when)),
+            LifecycleEvent::Restart(_) => Some(LifecycleEvent::Restart(when)),
+            LifecycleEvent::Test
+            | LifecycleEvent::Eval(_)
+            | LifecycleEvent::FirstError
+            | LifecycleEvent::Crash
+            | LifecycleEvent::Shutdown => None,
+        }
+    }
+
+    /// Can shell commands for this event start with `restart:`?
+    ///
+    /// Crash hooks are run by the process monitor rather than the `ghci` session, so there's
+    /// no previous instance of the command to stop.
+    fn supports_restart(&self) -> bool {
+        !matches!(self, LifecycleEvent::Crash)
+    }
+
     fn supported_kind(&self) -> Vec<CommandKind> {
         match self {
-            LifecycleEvent::Startup(When::Before) => vec![CommandKind::Shell],
-            LifecycleEvent::Startup(When::After)
+            LifecycleEvent::Startup(When::Before)
+            | LifecycleEvent::Crash
+            | LifecycleEvent::Shutdown => vec![CommandKind::Shell],
+            LifecycleEvent::Eval(When::AfterSuccess | When::AfterFailure) => vec![],
+            LifecycleEvent::Startup(When::After | When::AfterSuccess | When::AfterFailure)
             | LifecycleEvent::Test
             | LifecycleEvent::Reload(_)
-            | LifecycleEvent::Restart(_) => {
+            | LifecycleEvent::Restart(_)
+            | LifecycleEvent::Eval(When::Before | When::After)
+            | LifecycleEvent::FirstError => {
                 vec![CommandKind::Ghci, CommandKind::Shell]
             }
         }
@@ -118,6 +220,7 @@ impl LifecycleEvent {
             event.supported_kind().into_iter().map(move |kind| Hook {
                 event,
                 command: kind,
+                settings: Default::default(),
             })
         })
     }
@@ -130,13 +233,28 @@ pub enum When {
     Before,
     /// Run the hook after the event.
     After,
+    /// Run the hook after the event, if compilation succeeded.
+    AfterSuccess,
+    /// Run the hook after the event, if compilation failed.
+    AfterFailure,
 }
 
 impl Display for When {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             When::Before => write!(f, "before"),
-            When::After => write!(f, "after"),
+            When::After | When::AfterSuccess | When::AfterFailure => write!(f, "after"),
+        }
+    }
+}
+
+impl When {
+    /// Get the compilation outcome this hook is conditional on, like `success` or `failure`.
+    fn outcome(&self) -> Option<&'static str> {
+        match self {
+            When::Before | When::After => None,
+            When::AfterSuccess => Some("success"),
+            When::AfterFailure => Some("failure"),
         }
     }
 }
@@ -204,6 +322,8 @@ pub struct Hook<C> {
     pub event: LifecycleEvent,
     /// The command to run.
     pub command: C,
+    /// Settings for running the command.
+    pub settings: HookSettings,
 }
 
 impl<C> Display for Hook<C> {
@@ -217,10 +337,27 @@ impl<C> Hook<C> {
         Hook {
             event: self.event,
             command,
+            settings: self.settings,
         }
     }
 }
 
+impl Hook<Command> {
+    /// The name of this hook, like `after-reload-shell`.
+    pub fn name(&self) -> String {
+        self.with_command(self.command.kind()).arg_name()
+    }
+
+    /// Apply this hook's [`FailurePolicy`] to the result of running it.
+    ///
+    /// `success` is `None` if it's unknown if the hook succeeded, like for asynchronous shell
+    /// commands.
+    pub fn check_success(&self, success: Option<bool>) -> miette::Result<()> {
+        self.settings
+            .check_success(&self.name(), &self.command, success)
+    }
+}
+
 impl Hook<CommandKind> {
     fn extra_help(&self) -> Option<&'static str> {
         match (self.event, self.command) {
@@ -239,6 +376,11 @@ impl Hook<CommandKind> {
                 Example: `TestMain.testMain`.
                 ",
             )),
+            (LifecycleEvent::Shutdown, _) => Some(indoc!(
+                "
+                This can be used to stop services started by `async:` startup hooks.
+                ",
+            )),
             _ => None,
         }
         .map(|help| help.trim_end_matches('\n'))
@@ -249,7 +391,7 @@ impl Hook<CommandKind> {
     }
 
     fn help(&self) -> Help {
-        let Hook { event, command } = self;
+        let Hook { event, command, .. } = self;
         let kind = match command {
             CommandKind::Ghci => "`ghci`",
             CommandKind::Shell => "Shell",
@@ -269,6 +411,12 @@ impl Hook<CommandKind> {
             write!(short, "{}", event.event_name()).expect("Writing to a `String` never fails");
         }
 
+        match self.event.when() {
+            Some(When::AfterSuccess) => short.push_str(", if compilation succeeded"),
+            Some(When::AfterFailure) => short.push_str(", if compilation failed"),
+            _ => {}
+        }
+
         let mut long = short.clone();
 
         long.push_str("\n\n");
@@ -276,6 +424,12 @@ impl Hook<CommandKind> {
 
         if let CommandKind::Shell = command {
             long.push_str("\n\nCommands starting with `async:` will be run in the background.");
+            if event.supports_restart() {
+                long.push_str(
+                    " Commands starting with `restart:` will also stop the previous instance of \
+                    the command first.",
+                );
+            }
         }
 
         if let Some(extra_help) = self.extra_help() {
@@ -294,6 +448,241 @@ struct Help {
     long: String,
 }
 
+/// What to do when a hook fails or times out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Keep going without reporting the failure.
+    Ignore,
+    /// Report the failure and keep going.
+    #[default]
+    Warn,
+    /// Report the failure and skip the rest of the hooks and the reload, restart, or startup
+    /// they're a part of.
+    Abort,
+}
+
+impl FromStr for FailurePolicy {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "abort" => Ok(Self::Abort),
+            _ => Err(miette!(
+                "Invalid failure policy `{s}`; expected `ignore`, `warn`, or `abort`"
+            )),
+        }
+    }
+}
+
+impl Display for FailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailurePolicy::Ignore => write!(f, "ignore"),
+            FailurePolicy::Warn => write!(f, "warn"),
+            FailurePolicy::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+/// Settings for running a hook, given as prefixes of the hook's command like `timeout=30s:` or
+/// `on-failure=abort:`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HookSettings {
+    /// How long to let the hook run before interrupting it.
+    pub timeout: Option<Duration>,
+    /// What to do when the hook fails or times out.
+    pub on_failure: FailurePolicy,
+}
+
+impl HookSettings {
+    /// Apply the [`FailurePolicy`] to the result of running the hook named `hook`.
+    ///
+    /// `success` is `None` if it's unknown if the hook succeeded, like for asynchronous shell
+    /// commands.
+    pub fn check_success(
+        &self,
+        hook: &str,
+        command: impl Display,
+        success: Option<bool>,
+    ) -> miette::Result<()> {
+        if success != Some(false) {
+            return Ok(());
+        }
+        match self.on_failure {
+            // Failed commands are already reported when they finish.
+            FailurePolicy::Ignore | FailurePolicy::Warn => {}
+            FailurePolicy::Abort => {
+                tracing::error!("{hook} hook failed, aborting: {command}");
+                return Err(HookAborted {
+                    hook: hook.to_owned(),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply these settings to a shell command.
+    pub fn apply(&self, command: &MaybeAsyncCommand) -> MaybeAsyncCommand {
+        command
+            .clone()
+            .timeout(self.timeout)
+            .quiet(self.on_failure == FailurePolicy::Ignore)
+    }
+}
+
+/// Parse [`HookSettings`] prefixes from the start of a hook's command.
+fn hook_settings(input: &mut &str) -> PResult<HookSettings> {
+    let mut settings = HookSettings::default();
+    loop {
+        if let Some(timeout) = opt(preceded(
+            "timeout=",
+            cut_err(terminated(
+                take_until(1.., ":").try_map(humantime::parse_duration),
+                ":",
+            )),
+        ))
+        .parse_next(input)?
+        {
+            settings.timeout = Some(timeout);
+        } else if let Some(on_failure) = opt(preceded(
+            "on-failure=",
+            cut_err(terminated(take_until(1.., ":").parse_to(), ":")),
+        ))
+        .parse_next(input)?
+        {
+            settings.on_failure = on_failure;
+        } else {
+            return Ok(settings);
+        }
+    }
+}
+
+/// Parse a hook's command, with [`HookSettings`] prefixes.
+fn parse_hook_arg<C>(
+    input: &str,
+    parse_command: impl Fn(&str) -> miette::Result<C>,
+) -> miette::Result<(HookSettings, C)> {
+    let (settings, command) = (hook_settings, rest)
+        .parse(input)
+        .map_err(|err| miette!("{err}"))?;
+    Ok((settings, parse_command(command)?))
+}
+
+/// A shell command to run before reloading when paths matching a glob change, given as
+/// `GLOB=SHELL_CMD`.
+#[derive(Debug, Clone)]
+pub struct OnChangeHook {
+    /// The glob to match changed paths against.
+    pub glob: String,
+    /// Settings for running the command.
+    pub settings: HookSettings,
+    /// The command to run.
+    pub command: MaybeAsyncCommand,
+}
+
+impl OnChangeHook {
+    /// The name of this hook, for logging and events.
+    pub const NAME: &'static str = "on-change";
+}
+
+impl FromStr for OnChangeHook {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (glob, command) = s
+            .split_once('=')
+            .ok_or_else(|| miette!("Expected `GLOB=SHELL_CMD`, but got {s:?}"))?;
+        let (settings, command) = parse_hook_arg(command, MaybeAsyncCommand::from_str)?;
+        Ok(Self {
+            glob: glob.to_owned(),
+            settings,
+            command,
+        })
+    }
+}
+
+impl Display for OnChangeHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.glob, self.command)
+    }
+}
+
+/// A hook failed with [`FailurePolicy::Abort`].
+///
+/// The reload, restart, or startup the hook was run for is skipped, but `ghciwatch` keeps
+/// running.
+#[derive(Debug, Clone)]
+pub struct HookAborted {
+    /// The name of the hook, like `before-reload-shell`.
+    pub hook: String,
+}
+
+impl std::error::Error for HookAborted {}
+
+impl Display for HookAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} hook failed", self.hook)
+    }
+}
+
+impl miette::Diagnostic for HookAborted {}
+
+/// Information about why hooks are running.
+///
+/// This is exported to shell hooks as environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct HookContext {
+    /// Paths which changed, triggering the current reload or restart.
+    pub changed_paths: Vec<Utf8PathBuf>,
+    /// The result of compilation, if compilation has finished.
+    pub result: Option<CompilationResult>,
+    /// The number of errors emitted during compilation.
+    pub error_count: usize,
+    /// The number of warnings emitted during compilation.
+    pub warning_count: usize,
+    /// The path to the error file, if any.
+    pub error_file: Option<Utf8PathBuf>,
+}
+
+impl HookContext {
+    /// Get the environment variables to set for a shell hook run for the given event.
+    pub fn env(&self, event: impl Display) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("GHCIWATCH_EVENT", event.to_string()),
+            (
+                "GHCIWATCH_CHANGED_PATHS",
+                self.changed_paths
+                    .iter()
+                    .map(|path| path.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ];
+
+        if let Some(result) = self.result {
+            env.push((
+                "GHCIWATCH_RESULT",
+                match result {
+                    CompilationResult::Ok => "ok",
+                    CompilationResult::Err => "err",
+                }
+                .to_owned(),
+            ));
+            env.push(("GHCIWATCH_ERROR_COUNT", self.error_count.to_string()));
+            env.push(("GHCIWATCH_WARNING_COUNT", self.warning_count.to_string()));
+        }
+
+        if let Some(error_file) = &self.error_file {
+            env.push(("GHCIWATCH_ERROR_FILE", error_file.to_string()));
+        }
+
+        env
+    }
+}
+
 /// Lifecycle hooks.
 ///
 /// These are `ghci` and shell commands to run at various points in the `ghciwatch`
@@ -301,9 +690,25 @@ struct Help {
 #[derive(Debug, Clone, Default)]
 pub struct HookOpts {
     hooks: Vec<Hook<Command>>,
+    /// If given, record when synchronous shell hooks are running so that the file watcher can
+    /// ignore their writes to paths matching the globs.
+    windows: Option<(HookWindows, Arc<GlobMatcher>)>,
 }
 
 impl HookOpts {
+    /// Record when synchronous shell hooks are running in the given windows, so that their writes
+    /// to paths matching `globs` are ignored.
+    pub fn record_windows(&mut self, windows: HookWindows, globs: GlobMatcher) {
+        self.windows = Some((windows, Arc::new(globs)));
+    }
+
+    /// Start a window for a shell hook, if windows are being recorded.
+    pub fn hook_window(&self) -> Option<HookWindow> {
+        self.windows
+            .as_ref()
+            .map(|(windows, globs)| windows.start(WindowPaths::Matching(globs.clone())))
+    }
+
     pub fn select(&self, event: LifecycleEvent) -> impl Iterator<Item = &Hook<Command>> {
         self.hooks.iter().filter(move |hook| hook.event == event)
     }
@@ -311,12 +716,38 @@ impl HookOpts {
     pub async fn run_shell_hooks(
         &self,
         event: LifecycleEvent,
-        handles: &mut Vec<JoinHandle<miette::Result<ExitStatus>>>,
+        handles: &mut CommandHandles,
+        events: &EventStream,
+        context: &HookContext,
     ) -> miette::Result<()> {
         for hook in self.select(event) {
             if let Command::Shell(command) = &hook.command {
                 tracing::info!(%command, "Running {hook} command");
-                command.run_on(handles).await?;
+                events
+                    .emit(Event::HookStart {
+                        hook: hook.name(),
+                        command: command.to_string(),
+                    })
+                    .await;
+                let start_instant = Instant::now();
+                let window = self.hook_window();
+                let success = hook
+                    .settings
+                    .apply(command)
+                    .envs(context.env(event))
+                    .run_on(handles)
+                    .await?
+                    .map(|status| status.success());
+                drop(window);
+                events
+                    .emit(Event::HookFinish {
+                        hook: hook.name(),
+                        command: hook.command.to_string(),
+                        success,
+                        duration_ms: start_instant.elapsed(),
+                    })
+                    .await;
+                hook.check_success(success)?;
             }
         }
         Ok(())
@@ -338,8 +769,23 @@ impl Args for HookOpts {
                 .help_heading("Lifecycle hooks");
 
             let arg = match hook.command {
-                CommandKind::Ghci => arg.value_parser(GhciCommand::value_parser()),
-                CommandKind::Shell => arg.value_parser(MaybeAsyncCommand::from_str),
+                CommandKind::Ghci => arg.value_parser(|input: &str| {
+                    parse_hook_arg(input, |command| Ok(GhciCommand(command.to_owned())))
+                }),
+                CommandKind::Shell => {
+                    let event = hook.event;
+                    arg.value_parser(move |input: &str| {
+                        let (settings, command) =
+                            parse_hook_arg(input, MaybeAsyncCommand::from_str)?;
+                        if command.is_restartable && !event.supports_restart() {
+                            return Err(miette!(
+                                "`restart:` commands aren't supported for {event} hooks; \
+                                use `async:` instead"
+                            ));
+                        }
+                        Ok((settings, command))
+                    })
+                }
             };
 
             cmd = cmd.arg(arg);
@@ -366,19 +812,25 @@ impl FromArgMatches for HookOpts {
                 CommandKind::Ghci => {
                     self.hooks.extend(
                         matches
-                            .get_many::<GhciCommand>(&name)
+                            .get_many::<(HookSettings, GhciCommand)>(&name)
                             .into_iter()
                             .flatten()
-                            .map(|command| hook.with_command(Command::Ghci(command.clone()))),
+                            .map(|(settings, command)| Hook {
+                                settings: *settings,
+                                ..hook.with_command(Command::Ghci(command.clone()))
+                            }),
                     );
                 }
                 CommandKind::Shell => {
                     self.hooks.extend(
                         matches
-                            .get_many::<MaybeAsyncCommand>(&name)
+                            .get_many::<(HookSettings, MaybeAsyncCommand)>(&name)
                             .into_iter()
                             .flatten()
-                            .map(|command| hook.with_command(Command::Shell(command.clone()))),
+                            .map(|(settings, command)| Hook {
+                                settings: *settings,
+                                ..hook.with_command(Command::Shell(command.clone()))
+                            }),
                     );
                 }
             }
@@ -388,9 +840,156 @@ impl FromArgMatches for HookOpts {
         //
         // Shell commands _may_ be asynchronous, but `ghci` commands are always synchronous, so we
         // run shell commands first.
-        self.hooks
-            .sort_by(|a, b| a.command.kind().cmp(&b.command.kind()));
+        self.hooks.sort_by_key(|hook| hook.command.kind());
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_lifecycle_event_with_outcome() {
+        assert_eq!(
+            LifecycleEvent::Reload(When::After).with_outcome(Some(CompilationResult::Ok)),
+            Some(LifecycleEvent::Reload(When::AfterSuccess))
+        );
+        assert_eq!(
+            LifecycleEvent::Restart(When::After).with_outcome(Some(CompilationResult::Err)),
+            Some(LifecycleEvent::Restart(When::AfterFailure))
+        );
+        assert_eq!(
+            LifecycleEvent::Startup(When::After).with_outcome(None),
+            Some(LifecycleEvent::Startup(When::AfterSuccess))
+        );
+        assert_eq!(
+            LifecycleEvent::Reload(When::Before).with_outcome(Some(CompilationResult::Ok)),
+            None
+        );
+        assert_eq!(
+            LifecycleEvent::Test.with_outcome(Some(CompilationResult::Ok)),
+            None
+        );
+        assert_eq!(
+            LifecycleEvent::Eval(When::After).with_outcome(Some(CompilationResult::Ok)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hook_arg_names() {
+        let names = LifecycleEvent::hooks()
+            .map(|hook| hook.arg_name())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"after-reload-success-shell".to_owned()));
+        assert!(names.contains(&"after-reload-failure-ghci".to_owned()));
+        assert!(names.contains(&"after-startup-success-shell".to_owned()));
+        assert!(names.contains(&"after-restart-failure-shell".to_owned()));
+        assert!(!names.contains(&"before-startup-ghci".to_owned()));
+        assert!(names.contains(&"before-eval-ghci".to_owned()));
+        assert!(names.contains(&"after-eval-shell".to_owned()));
+        assert!(!names.contains(&"after-eval-success-shell".to_owned()));
+        assert!(names.contains(&"first-error-ghci".to_owned()));
+        assert!(names.contains(&"crash-shell".to_owned()));
+        assert!(!names.contains(&"crash-ghci".to_owned()));
+        assert!(names.contains(&"shutdown-shell".to_owned()));
+        assert!(!names.contains(&"shutdown-ghci".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_hook_arg() {
+        let parse = |input: &str| parse_hook_arg(input, |command| Ok(command.to_owned())).unwrap();
+
+        assert_eq!(
+            parse("TestMain.testMain"),
+            (HookSettings::default(), "TestMain.testMain".to_owned())
+        );
+        assert_eq!(
+            parse("timeout=30s:on-failure=abort:async:cabal-fmt --check"),
+            (
+                HookSettings {
+                    timeout: Some(Duration::from_secs(30)),
+                    on_failure: FailurePolicy::Abort,
+                },
+                "async:cabal-fmt --check".to_owned()
+            )
+        );
+        assert_eq!(
+            parse("on-failure=ignore:timeout=1m 30s::set -Wall"),
+            (
+                HookSettings {
+                    timeout: Some(Duration::from_secs(90)),
+                    on_failure: FailurePolicy::Ignore,
+                },
+                ":set -Wall".to_owned()
+            )
+        );
+
+        assert!(parse_hook_arg("timeout=puppy:ls", |command| Ok(command.to_owned())).is_err());
+        assert!(parse_hook_arg("on-failure=puppy:ls", |command| Ok(command.to_owned())).is_err());
+    }
+
+    #[test]
+    fn test_crash_hooks_cant_restart() {
+        let parse = |args: &[&str]| {
+            HookOpts::augment_args(clap::Command::new("ghciwatch"))
+                .try_get_matches_from(std::iter::once("ghciwatch").chain(args.iter().copied()))
+        };
+
+        assert!(parse(&["--crash-shell", "async:notify-send crashed"]).is_ok());
+        assert!(parse(&["--crash-shell", "restart:notify-send crashed"]).is_err());
+        assert!(parse(&["--after-startup-shell", "restart:cabal run server"]).is_ok());
+    }
+
+    #[test]
+    fn test_parse_on_change_hook() {
+        let hook = "**/*.proto=timeout=1m:make bindings"
+            .parse::<OnChangeHook>()
+            .unwrap();
+        assert_eq!(hook.glob, "**/*.proto");
+        assert_eq!(hook.settings.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(
+            hook.command,
+            "make bindings".parse::<MaybeAsyncCommand>().unwrap()
+        );
+
+        assert!("make bindings".parse::<OnChangeHook>().is_err());
+    }
+
+    #[test]
+    fn test_hook_context_env() {
+        assert_eq!(
+            HookContext::default().env(LifecycleEvent::Reload(When::Before)),
+            vec![
+                ("GHCIWATCH_EVENT", "before-reload".to_owned()),
+                ("GHCIWATCH_CHANGED_PATHS", String::new()),
+            ]
+        );
+
+        assert_eq!(
+            HookContext {
+                changed_paths: vec!["src/MyLib.hs".into(), "src/My Module.hs".into()],
+                result: Some(CompilationResult::Err),
+                error_count: 1,
+                warning_count: 2,
+                error_file: Some("ghcid.txt".into()),
+            }
+            .env(LifecycleEvent::Reload(When::After)),
+            vec![
+                ("GHCIWATCH_EVENT", "after-reload".to_owned()),
+                (
+                    "GHCIWATCH_CHANGED_PATHS",
+                    "src/MyLib.hs\nsrc/My Module.hs".to_owned()
+                ),
+                ("GHCIWATCH_RESULT", "err".to_owned()),
+                ("GHCIWATCH_ERROR_COUNT", "1".to_owned()),
+                ("GHCIWATCH_WARNING_COUNT", "2".to_owned()),
+                ("GHCIWATCH_ERROR_FILE", "ghcid.txt".to_owned()),
+            ]
+        );
+    }
+}
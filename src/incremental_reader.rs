@@ -16,6 +16,9 @@ use crate::buffers::LINE_BUFFER_CAPACITY;
 use crate::buffers::SPLIT_UTF8_CODEPOINT_CAPACITY;
 use crate::buffers::VEC_BUFFER_CAPACITY;
 
+/// A hook called with each line before it's written; see [`IncrementalReader::with_line_hook`].
+type LineHook = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
 /// A tool for incrementally reading from a stream like stdout (and forwarding that stream to a
 /// writer).
 ///
@@ -34,6 +37,8 @@ pub struct IncrementalReader<R, W> {
     reader: Pin<Box<R>>,
     /// The wrapped writer, if any.
     writer: Option<Pin<Box<W>>>,
+    /// Called with each line before it's written; see [`Self::with_line_hook`].
+    line_hook: Option<LineHook>,
     /// Lines we've already read since the last marker/chunk.
     lines: String,
     /// The line currently being written to.
@@ -57,6 +62,7 @@ where
         Self {
             reader: Box::pin(reader),
             writer: None,
+            line_hook: None,
             lines: String::with_capacity(VEC_BUFFER_CAPACITY * LINE_BUFFER_CAPACITY),
             line: String::with_capacity(LINE_BUFFER_CAPACITY),
             line_stripped: String::with_capacity(LINE_BUFFER_CAPACITY),
@@ -74,6 +80,18 @@ where
         }
     }
 
+    /// Add a hook to this reader, which is called with each line before it's written to the
+    /// writer. If the hook returns a string, it's written instead of the line and its newline.
+    pub fn with_line_hook(
+        self,
+        hook: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            line_hook: Some(Box::new(hook)),
+            ..self
+        }
+    }
+
     /// Get the ANSI-stripped version of the current line, using the cache if valid.
     fn get_stripped_line(&mut self) -> &str {
         if !self.line_stripped_valid {
@@ -278,18 +296,12 @@ where
 
     /// Clears `self.lines` and `self.line`, returning the previous value of `self.lines`.
     async fn take_lines(&mut self, writing: WriteBehavior) -> miette::Result<String> {
-        if let Some(writer) = &mut self.writer {
-            match writing {
-                WriteBehavior::Write => {
-                    writer
-                        .write_all(self.line.as_bytes())
-                        .await
-                        .into_diagnostic()?;
-                    // We'll just pretend this is the end of the line...
-                    writer.write_all(b"\n").await.into_diagnostic()?;
-                }
-                WriteBehavior::NoFinalLine | WriteBehavior::Hide => {}
+        match writing {
+            WriteBehavior::Write => {
+                // We'll just pretend this is the end of the line...
+                self.write_line().await?;
             }
+            WriteBehavior::NoFinalLine | WriteBehavior::Hide => {}
         }
 
         self.line.clear();
@@ -303,20 +315,36 @@ where
         Ok(ret)
     }
 
-    /// Add `self.line` to `self.lines`, replacing `self.line` with an empty buffer.
-    async fn finish_line(&mut self, writing: WriteBehavior) -> miette::Result<()> {
+    /// Write `self.line` and a newline to the writer, if any.
+    async fn write_line(&mut self) -> miette::Result<()> {
         if let Some(writer) = &mut self.writer {
-            match writing {
-                WriteBehavior::Write | WriteBehavior::NoFinalLine => {
+            match self.line_hook.as_ref().and_then(|hook| hook(&self.line)) {
+                Some(replacement) => {
+                    writer
+                        .write_all(replacement.as_bytes())
+                        .await
+                        .into_diagnostic()?;
+                }
+                None => {
                     writer
                         .write_all(self.line.as_bytes())
                         .await
                         .into_diagnostic()?;
                     writer.write_all(b"\n").await.into_diagnostic()?;
                 }
-                WriteBehavior::Hide => {}
             }
         }
+        Ok(())
+    }
+
+    /// Add `self.line` to `self.lines`, replacing `self.line` with an empty buffer.
+    async fn finish_line(&mut self, writing: WriteBehavior) -> miette::Result<()> {
+        match writing {
+            WriteBehavior::Write | WriteBehavior::NoFinalLine => {
+                self.write_line().await?;
+            }
+            WriteBehavior::Hide => {}
+        }
 
         // Optimization: Instead of creating a temporary String by replacing self.line,
         // directly append self.line to self.lines and then clear it. This avoids an
@@ -16,19 +16,24 @@
 
 mod aho_corasick;
 mod buffers;
+mod byte_size;
 mod clap;
 pub mod clap_markdown;
 pub mod cli;
 mod clonable_command;
 mod command_ext;
+mod config;
+mod control_socket;
 mod cwd;
 mod event_filter;
 mod format_bulleted_list;
 mod ghci;
 mod haskell_source_file;
+mod hook_windows;
 mod hooks;
 mod ignore;
 mod incremental_reader;
+mod lsp;
 mod maybe_async_command;
 mod normal_path;
 mod shutdown;
@@ -42,10 +47,14 @@ pub(crate) use cwd::current_dir_utf8;
 pub(crate) use format_bulleted_list::format_bulleted_list;
 pub(crate) use string_case::StringCase;
 
+pub use control_socket::run_control_socket;
 pub use ghci::manager::run_ghci;
 pub use ghci::Ghci;
 pub use ghci::GhciOpts;
 pub use ghci::GhciWriter;
+pub use hook_windows::HookWindows;
+pub use lsp::run_lsp;
+pub use lsp::LspTarget;
 pub use shutdown::ShutdownError;
 pub use shutdown::ShutdownHandle;
 pub use shutdown::ShutdownManager;
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+
+use crate::ghci::parse::CompilationResult;
+use crate::ghci::parse::GhcDiagnostic;
+use crate::ghci::parse::Severity;
+use crate::ghci::CompilationLog;
+use crate::normal_path::NormalPath;
+
+/// The diagnostics currently published for each file, keyed by absolute path.
+///
+/// `ghci` only reports diagnostics for the modules it compiles, so each [`CompilationLog`] is
+/// merged into the existing state rather than replacing it:
+///
+/// - Files with diagnostics in the log have their diagnostics replaced.
+/// - Files which were compiled without any diagnostics are cleared.
+/// - If compilation succeeded, no errors remain anywhere, so errors are cleared from every file.
+///   Warnings for files which weren't recompiled are kept.
+#[derive(Debug, Default)]
+pub struct DiagnosticStore {
+    files: BTreeMap<Utf8PathBuf, Vec<GhcDiagnostic>>,
+}
+
+impl DiagnosticStore {
+    /// Merge a compilation log into the store.
+    ///
+    /// Paths in the log are resolved relative to `cwd`. Returns the set of files whose
+    /// diagnostics changed and need to be republished.
+    pub fn update(&mut self, cwd: &Utf8Path, log: &CompilationLog) -> BTreeSet<Utf8PathBuf> {
+        let mut changed = BTreeSet::new();
+
+        let mut new_files: BTreeMap<Utf8PathBuf, Vec<GhcDiagnostic>> = BTreeMap::new();
+        for diagnostic in &log.diagnostics {
+            if let Some(path) = diagnostic
+                .path
+                .as_deref()
+                .and_then(|path| absolute(cwd, path))
+            {
+                new_files.entry(path).or_default().push(diagnostic.clone());
+            }
+        }
+
+        if let Some(CompilationResult::Ok) = log.result() {
+            for (path, diagnostics) in self.files.iter_mut() {
+                let count = diagnostics.len();
+                diagnostics.retain(|diagnostic| diagnostic.severity != Severity::Error);
+                if diagnostics.len() != count {
+                    changed.insert(path.clone());
+                }
+            }
+        }
+
+        for module in &log.compiled_modules {
+            if let Some(path) = absolute(cwd, &module.path) {
+                if !new_files.contains_key(&path) && self.files.remove(&path).is_some() {
+                    changed.insert(path);
+                }
+            }
+        }
+
+        for (path, diagnostics) in new_files {
+            if self.files.get(&path) != Some(&diagnostics) {
+                changed.insert(path.clone());
+            }
+            self.files.insert(path, diagnostics);
+        }
+
+        self.files.retain(|_, diagnostics| !diagnostics.is_empty());
+
+        changed
+    }
+
+    /// Get the diagnostics for the given file.
+    pub fn get(&self, path: &Utf8Path) -> &[GhcDiagnostic] {
+        self.files.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Iterate over the files with diagnostics.
+    pub fn paths(&self) -> impl Iterator<Item = &Utf8PathBuf> {
+        self.files.keys()
+    }
+}
+
+fn absolute(cwd: &Utf8Path, path: &Utf8Path) -> Option<Utf8PathBuf> {
+    match NormalPath::new(path, cwd) {
+        Ok(path) => Some(path.into_absolute()),
+        Err(err) => {
+            tracing::debug!(%path, "Failed to resolve diagnostic path: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ghci::parse::CompilationSummary;
+    use crate::ghci::parse::CompilingModule;
+    use crate::ghci::parse::ModulesLoaded;
+    use crate::ghci::parse::PositionRange;
+    use pretty_assertions::assert_eq;
+
+    fn diagnostic(severity: Severity, path: &str) -> GhcDiagnostic {
+        GhcDiagnostic {
+            severity,
+            path: Some(path.into()),
+            span: PositionRange::new(1, 1, 1, 1),
+            message: "puppy".into(),
+            ..Default::default()
+        }
+    }
+
+    fn log(
+        result: CompilationResult,
+        compiled: &[&str],
+        diagnostics: Vec<GhcDiagnostic>,
+    ) -> CompilationLog {
+        CompilationLog {
+            summary: Some(CompilationSummary {
+                result,
+                modules_loaded: ModulesLoaded::Count(compiled.len()),
+            }),
+            diagnostics,
+            compiled_modules: compiled
+                .iter()
+                .map(|path| CompilingModule {
+                    name: "M".into(),
+                    path: (*path).into(),
+                })
+                .collect(),
+            module_times: vec![],
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let cwd = Utf8Path::new("/project");
+        let a = Utf8PathBuf::from("/project/src/A.hs");
+        let b = Utf8PathBuf::from("/project/src/B.hs");
+        let mut store = DiagnosticStore::default();
+
+        // A has a warning, B has an error.
+        let changed = store.update(
+            cwd,
+            &log(
+                CompilationResult::Err,
+                &["src/A.hs", "src/B.hs"],
+                vec![
+                    diagnostic(Severity::Warning, "src/A.hs"),
+                    diagnostic(Severity::Error, "src/B.hs"),
+                ],
+            ),
+        );
+        assert_eq!(changed, [a.clone(), b.clone()].into());
+        assert_eq!(store.get(&a).len(), 1);
+        assert_eq!(store.get(&b).len(), 1);
+
+        // Only B is recompiled and the error is fixed; A keeps its warning.
+        let changed = store.update(cwd, &log(CompilationResult::Ok, &["src/B.hs"], vec![]));
+        assert_eq!(changed, [b.clone()].into());
+        assert_eq!(store.get(&a).len(), 1);
+        assert!(store.get(&b).is_empty());
+        assert_eq!(store.paths().collect::<Vec<_>>(), vec![&a]);
+
+        // Republishing identical diagnostics isn't a change.
+        let changed = store.update(
+            cwd,
+            &log(
+                CompilationResult::Ok,
+                &["src/A.hs"],
+                vec![diagnostic(Severity::Warning, "src/A.hs")],
+            ),
+        );
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn test_update_success_clears_errors() {
+        let cwd = Utf8Path::new("/project");
+        let a = Utf8PathBuf::from("/project/src/A.hs");
+        let mut store = DiagnosticStore::default();
+
+        store.update(
+            cwd,
+            &log(
+                CompilationResult::Err,
+                &[],
+                vec![
+                    diagnostic(Severity::Warning, "src/A.hs"),
+                    diagnostic(Severity::Error, "src/A.hs"),
+                ],
+            ),
+        );
+        assert_eq!(store.get(&a).len(), 2);
+
+        // The module is removed, so it's never recompiled, but compilation succeeds.
+        let changed = store.update(cwd, &log(CompilationResult::Ok, &[], vec![]));
+        assert_eq!(changed, [a.clone()].into());
+        assert_eq!(store.get(&a), &[diagnostic(Severity::Warning, "src/A.hs")]);
+    }
+}
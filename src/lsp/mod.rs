@@ -0,0 +1,476 @@
+//! A Language Server Protocol front-end which publishes diagnostics from the `ghci` session.
+//!
+//! This is deliberately minimal: we don't implement any language features, we only push
+//! `textDocument/publishDiagnostics` notifications whenever a compilation finishes. A single
+//! `ghciwatch` process can serve any number of editors when listening on a Unix socket.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde_json::json;
+use serde_json::Value;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::BufReader;
+use tokio::net::UnixListener;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tracing::instrument;
+
+use crate::ghci::parse::GhcDiagnostic;
+use crate::ghci::parse::Position;
+use crate::ghci::parse::PositionRange;
+use crate::ghci::parse::Severity;
+use crate::ghci::CompilationLog;
+use crate::shutdown::ShutdownHandle;
+
+mod diagnostic_store;
+use diagnostic_store::DiagnosticStore;
+
+mod transport;
+use transport::read_message;
+use transport::write_message;
+
+/// Where to serve the Language Server Protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspTarget {
+    /// Communicate with a single editor over `stdin` and `stdout`.
+    ///
+    /// In this mode, `ghci` output is written to `stderr` instead of `stdout`.
+    Stdio,
+    /// Listen for editor connections on a Unix domain socket at the given path.
+    Socket(Utf8PathBuf),
+}
+
+impl FromStr for LspTarget {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err(miette!("LSP target must be `stdio` or a socket path")),
+            "stdio" | "-" => Ok(Self::Stdio),
+            path => Ok(Self::Socket(path.into())),
+        }
+    }
+}
+
+impl Display for LspTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LspTarget::Stdio => write!(f, "stdio"),
+            LspTarget::Socket(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Diagnostics from a finished compilation, sent from [`crate::Ghci`] to [`run_lsp`].
+#[derive(Debug, Clone)]
+pub struct DiagnosticsUpdate {
+    /// The `ghci` session's working directory, which diagnostic paths are relative to.
+    pub cwd: Utf8PathBuf,
+    /// The compilation log.
+    pub log: CompilationLog,
+}
+
+/// An event sent from a client connection task to the server task.
+enum ClientEvent {
+    /// The client finished initializing and can receive notifications.
+    Initialized {
+        id: usize,
+        sender: mpsc::Sender<Value>,
+    },
+    /// The client disconnected.
+    Disconnected { id: usize },
+}
+
+/// Start the LSP server, publishing diagnostics from the given receiver.
+#[instrument(level = "debug", skip_all)]
+pub async fn run_lsp(
+    mut handle: ShutdownHandle,
+    target: LspTarget,
+    mut receiver: mpsc::Receiver<DiagnosticsUpdate>,
+) -> miette::Result<()> {
+    let (client_sender, mut client_receiver) = mpsc::channel(8);
+    let mut connections = JoinSet::new();
+    let mut next_id = 0;
+
+    let listener = match &target {
+        LspTarget::Stdio => {
+            connections.spawn(serve_client(
+                next_id,
+                tokio::io::stdin(),
+                tokio::io::stdout(),
+                client_sender.clone(),
+            ));
+            next_id += 1;
+            None
+        }
+        LspTarget::Socket(path) => Some(bind(path)?),
+    };
+    tracing::info!(%target, "Serving diagnostics over LSP");
+
+    let mut store = DiagnosticStore::default();
+    let mut clients: BTreeMap<usize, mpsc::Sender<Value>> = BTreeMap::new();
+
+    loop {
+        tokio::select! {
+            _ = handle.on_shutdown_requested() => {
+                break;
+            }
+            Some(update) = receiver.recv() => {
+                let changed = store.update(&update.cwd, &update.log);
+                tracing::debug!(files = changed.len(), "Publishing LSP diagnostics");
+                for path in changed {
+                    let notification = publish_diagnostics(&path, store.get(&path));
+                    // Drop clients which have gone away.
+                    let mut disconnected = Vec::new();
+                    for (id, sender) in &clients {
+                        if sender.send(notification.clone()).await.is_err() {
+                            disconnected.push(*id);
+                        }
+                    }
+                    for id in disconnected {
+                        clients.remove(&id);
+                    }
+                }
+            }
+            Some(event) = client_receiver.recv() => {
+                match event {
+                    ClientEvent::Initialized { id, sender } => {
+                        tracing::debug!(id, "LSP client initialized");
+                        for path in store.paths() {
+                            let _ = sender.send(publish_diagnostics(path, store.get(path))).await;
+                        }
+                        clients.insert(id, sender);
+                    }
+                    ClientEvent::Disconnected { id } => {
+                        tracing::debug!(id, "LSP client disconnected");
+                        clients.remove(&id);
+                        if target == LspTarget::Stdio {
+                            // The editor that started us has gone away.
+                            let _ = handle.request_shutdown();
+                        }
+                    }
+                }
+            }
+            Ok((stream, _address)) = async {
+                match &listener {
+                    Some(listener) => listener.accept().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let (reader, writer) = stream.into_split();
+                connections.spawn(serve_client(next_id, reader, writer, client_sender.clone()));
+                next_id += 1;
+            }
+            Some(result) = connections.join_next() => {
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => tracing::debug!("LSP connection failed: {err:?}"),
+                    Err(err) => tracing::debug!("LSP connection task failed: {err}"),
+                }
+            }
+        }
+    }
+
+    if let LspTarget::Socket(path) = &target {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(())
+}
+
+/// Bind a Unix socket at the given path, removing a stale socket if one exists.
+fn bind(path: &Utf8Path) -> miette::Result<UnixListener> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to remove stale LSP socket {path}"))?;
+    }
+    UnixListener::bind(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to listen for LSP connections on {path}"))
+}
+
+/// Respond to requests from a single client until it exits or disconnects.
+async fn serve_client<R, W>(
+    id: usize,
+    reader: R,
+    writer: W,
+    server: mpsc::Sender<ClientEvent>,
+) -> miette::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, mut outgoing) = mpsc::channel::<Value>(64);
+    let writer_task = tokio::spawn(async move {
+        let mut writer = writer;
+        while let Some(message) = outgoing.recv().await {
+            write_message(&mut writer, &message).await?;
+        }
+        Ok::<_, miette::Report>(())
+    });
+
+    let result = handle_requests(id, BufReader::new(reader), &sender, &server).await;
+
+    let _ = server.send(ClientEvent::Disconnected { id }).await;
+    // The server drops its sender when it sees the disconnect, which lets the writer task finish
+    // flushing any outstanding responses.
+    drop(sender);
+    writer_task.await.into_diagnostic()??;
+
+    result
+}
+
+async fn handle_requests<R>(
+    id: usize,
+    mut reader: BufReader<R>,
+    sender: &mpsc::Sender<Value>,
+    server: &mpsc::Sender<ClientEvent>,
+) -> miette::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    while let Some(message) = read_message(&mut reader).await? {
+        let method = message.get("method").and_then(Value::as_str);
+        let request_id = message.get("id").cloned();
+        tracing::trace!(?method, "Got LSP message");
+
+        let response = match (method, request_id) {
+            (Some("initialize"), Some(request_id)) => response(
+                request_id,
+                json!({
+                    "capabilities": {
+                        // We don't need to know about open documents.
+                        "textDocumentSync": 0,
+                    },
+                    "serverInfo": {
+                        "name": "ghciwatch",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            ),
+            (Some("initialized"), None) => {
+                server
+                    .send(ClientEvent::Initialized {
+                        id,
+                        sender: sender.clone(),
+                    })
+                    .await
+                    .into_diagnostic()?;
+                continue;
+            }
+            (Some("shutdown"), Some(request_id)) => response(request_id, Value::Null),
+            (Some("exit"), None) => break,
+            (Some(method), Some(request_id)) => json!({
+                "jsonrpc": "2.0",
+                "id": request_id,
+                "error": {
+                    // `MethodNotFound`.
+                    "code": -32601,
+                    "message": format!("ghciwatch does not support {method}"),
+                },
+            }),
+            // Other notifications and responses to (nonexistent) server requests are ignored.
+            _ => continue,
+        };
+
+        sender.send(response).await.into_diagnostic()?;
+    }
+
+    Ok(())
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": result,
+    })
+}
+
+/// Construct a `textDocument/publishDiagnostics` notification for the given file.
+fn publish_diagnostics(path: &Utf8Path, diagnostics: &[GhcDiagnostic]) -> Value {
+    // The file's contents are needed to convert GHC's columns into UTF-16 offsets. If we can't
+    // read it, the columns are used as-is, which is only wrong for lines with non-ASCII text.
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+    let lines = contents.lines().collect::<Vec<_>>();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": file_uri(path),
+            "diagnostics": diagnostics
+                .iter()
+                .map(|diagnostic| lsp_diagnostic(diagnostic, &lines))
+                .collect::<Vec<_>>(),
+        },
+    })
+}
+
+/// Convert a GHC diagnostic into an LSP diagnostic, given the lines of the file it's in.
+fn lsp_diagnostic(diagnostic: &GhcDiagnostic, lines: &[&str]) -> Value {
+    let mut result = json!({
+        "range": lsp_range(&diagnostic.span, lines),
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "source": "ghc",
+        "message": diagnostic.message.trim_start_matches('\n').trim_end(),
+    });
+
+    if let Some(code) = diagnostic.code {
+        result["code"] = code.to_string().into();
+        result["codeDescription"] = json!({ "href": code.url() });
+    }
+
+    result
+}
+
+/// Convert a GHC span into an LSP range.
+///
+/// GHC positions are 1-based and GHC's end columns are inclusive. LSP positions are 0-based and
+/// LSP's end positions are exclusive, so the end column stays the same.
+///
+/// GHC columns count characters, but LSP columns count UTF-16 code units, so columns are
+/// converted using the text of the line they're on.
+fn lsp_range(span: &PositionRange, lines: &[&str]) -> Value {
+    let position = |position: Position, column: usize| {
+        let line = position.line().saturating_sub(1);
+        json!({
+            "line": line,
+            "character": match lines.get(line) {
+                Some(text) => utf16_column(text, column),
+                None => column,
+            },
+        })
+    };
+
+    let start = span.start();
+    let end = span.end();
+    json!({
+        "start": position(start, start.column().saturating_sub(1)),
+        "end": position(end, end.column()),
+    })
+}
+
+/// Convert a 0-based GHC column on the given line into a count of UTF-16 code units.
+///
+/// GHC advances tabs to the next multiple of 8 columns.
+fn utf16_column(line: &str, column: usize) -> usize {
+    let mut ghc_column = 0;
+    let mut utf16_column = 0;
+    for c in line.chars() {
+        if ghc_column >= column {
+            return utf16_column;
+        }
+        ghc_column = if c == '\t' {
+            (ghc_column / 8 + 1) * 8
+        } else {
+            ghc_column + 1
+        };
+        utf16_column += c.len_utf16();
+    }
+    // Past the end of the line.
+    utf16_column + column.saturating_sub(ghc_column)
+}
+
+/// Format an absolute path as a `file://` URI.
+pub(crate) fn file_uri(path: &Utf8Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.as_str().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ghci::parse::DiagnosticCode;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_lsp_target_from_str() {
+        assert_eq!("stdio".parse::<LspTarget>().unwrap(), LspTarget::Stdio);
+        assert_eq!(
+            "/tmp/ghciwatch.sock".parse::<LspTarget>().unwrap(),
+            LspTarget::Socket("/tmp/ghciwatch.sock".into())
+        );
+        assert!("".parse::<LspTarget>().is_err());
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(
+            file_uri(Utf8Path::new("/home/puppy/src/My Lib.hs")),
+            "file:///home/puppy/src/My%20Lib.hs"
+        );
+    }
+
+    #[test]
+    fn test_lsp_diagnostic() {
+        assert_eq!(
+            lsp_diagnostic(
+                &GhcDiagnostic {
+                    severity: Severity::Error,
+                    path: Some("src/MyModule.hs".into()),
+                    span: PositionRange::new(4, 11, 4, 19),
+                    message: "[GHC-83865]\n    • Couldn't match type\n".into(),
+                    code: Some(DiagnosticCode::new(83865)),
+                    ..Default::default()
+                },
+                &[]
+            ),
+            json!({
+                "range": {
+                    "start": {"line": 3, "character": 10},
+                    "end": {"line": 3, "character": 19},
+                },
+                "severity": 1,
+                "source": "ghc",
+                "message": "[GHC-83865]\n    • Couldn't match type",
+                "code": "GHC-83865",
+                "codeDescription": {
+                    "href": "https://errors.haskell.org/messages/GHC-83865",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_utf16() {
+        // `λ` is one UTF-16 code unit, `𝔸` is two, and the tab advances to column 9.
+        let lines = ["", "f 𝔸 = λ\tundefined"];
+        assert_eq!(
+            lsp_range(&PositionRange::new(2, 5, 2, 17), &lines),
+            json!({
+                "start": {"line": 1, "character": 5},
+                "end": {"line": 1, "character": 18},
+            })
+        );
+
+        // Without the file's contents, columns are used as-is.
+        assert_eq!(
+            lsp_range(&PositionRange::new(2, 5, 2, 17), &[]),
+            json!({
+                "start": {"line": 1, "character": 4},
+                "end": {"line": 1, "character": 17},
+            })
+        );
+    }
+}
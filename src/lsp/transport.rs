@@ -0,0 +1,150 @@
+//! JSON-RPC message framing for the Language Server Protocol.
+//!
+//! Each message is a JSON body preceded by a `Content-Length` header and a blank line:
+//!
+//! ```text
+//! Content-Length: 52\r\n
+//! \r\n
+//! {"jsonrpc":"2.0","method":"initialized","params":{}}
+//! ```
+//!
+//! See: <https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#baseProtocol>
+
+use miette::miette;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde_json::Value;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+/// Read a single message from the given reader.
+///
+/// Returns `None` if the stream is closed before a message starts.
+pub async fn read_message<R>(reader: &mut R) -> miette::Result<Option<Value>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let bytes = reader
+            .read_line(&mut line)
+            .await
+            .into_diagnostic()
+            .wrap_err("Failed to read LSP message header")?;
+        if bytes == 0 {
+            if content_length.is_none() {
+                return Ok(None);
+            }
+            return Err(miette!(
+                "LSP stream closed in the middle of a message header"
+            ));
+        }
+
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            // The blank line separating the headers from the body.
+            break;
+        }
+
+        // Header names are case-insensitive. The only other header is `Content-Type`, which we
+        // ignore.
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .into_diagnostic()
+                        .wrap_err_with(|| format!("Invalid `Content-Length` header: {header:?}"))?,
+                );
+            }
+        }
+    }
+
+    let content_length = content_length
+        .ok_or_else(|| miette!("LSP message is missing a `Content-Length` header"))?;
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to read LSP message body")?;
+
+    serde_json::from_slice(&body)
+        .into_diagnostic()
+        .wrap_err("Failed to parse LSP message body as JSON")
+        .map(Some)
+}
+
+/// Write a single message to the given writer and flush it.
+pub async fn write_message<W>(writer: &mut W, message: &Value) -> miette::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let body = message.to_string();
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes())
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to write LSP message")?;
+    writer
+        .flush()
+        .await
+        .into_diagnostic()
+        .wrap_err("Failed to flush LSP message")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_read_message() {
+        let mut input: &[u8] = b"Content-Length: 17\r\n\r\n{\"method\":\"exit\"}\
+            Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n\
+            content-length: 2\r\n\r\n{}";
+
+        assert_eq!(
+            read_message(&mut input).await.unwrap(),
+            Some(json!({"method": "exit"}))
+        );
+        assert_eq!(read_message(&mut input).await.unwrap(), Some(json!({})));
+        assert_eq!(read_message(&mut input).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_message_errors() {
+        // No `Content-Length`.
+        let mut input: &[u8] = b"Content-Type: puppy\r\n\r\n{}";
+        assert!(read_message(&mut input).await.is_err());
+
+        // Truncated body.
+        let mut input: &[u8] = b"Content-Length: 100\r\n\r\n{}";
+        assert!(read_message(&mut input).await.is_err());
+
+        // Truncated headers.
+        let mut input: &[u8] = b"Content-Length: 2\r\n";
+        assert!(read_message(&mut input).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_message() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({"id": 1})).await.unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Content-Length: 8\r\n\r\n{\"id\":1}"
+        );
+    }
+}
@@ -7,12 +7,14 @@
 use std::time::Duration;
 
 use clap::CommandFactory;
-use clap::Parser;
 use ghciwatch::cli;
+use ghciwatch::run_control_socket;
 use ghciwatch::run_ghci;
+use ghciwatch::run_lsp;
 use ghciwatch::run_tui;
 use ghciwatch::run_watcher;
 use ghciwatch::GhciOpts;
+use ghciwatch::HookWindows;
 use ghciwatch::ShutdownManager;
 use ghciwatch::TracingOpts;
 use ghciwatch::WatcherOpts;
@@ -21,10 +23,14 @@ use tokio::sync::mpsc;
 #[tokio::main]
 async fn main() -> miette::Result<()> {
     miette::set_panic_hook();
-    let mut opts = cli::Opts::parse();
+    let mut opts = cli::Opts::parse_with_config()?;
     opts.init()?;
     let (maybe_tracing_reader, _tracing_guard) = TracingOpts::from_cli(&opts).install()?;
 
+    if let Some(path) = &opts.config {
+        tracing::debug!(%path, "Loaded configuration file");
+    }
+
     #[cfg(feature = "clap-markdown")]
     if opts.generate_markdown_help {
         println!("{}", ghciwatch::clap_markdown::help_markdown::<cli::Opts>());
@@ -53,11 +59,28 @@ async fn main() -> miette::Result<()> {
 
     let (ghci_sender, ghci_receiver) = mpsc::channel(32);
 
-    let (ghci_opts, maybe_ghci_reader) = GhciOpts::from_cli(&opts).await?;
-    let watcher_opts = WatcherOpts::from_cli(&opts);
+    let (mut ghci_opts, maybe_ghci_reader) = GhciOpts::from_cli(&opts).await?;
+    let mut watcher_opts = WatcherOpts::from_cli(&opts);
+
+    let mut hook_windows = ghci_opts.write_eval_results.clone();
+    if !opts.watch.ignore_hook_writes.is_empty() {
+        let hook_windows = hook_windows.get_or_insert_with(HookWindows::default);
+        ghci_opts
+            .hooks
+            .record_windows(hook_windows.clone(), opts.watch.ignore_hook_writes()?);
+    }
+    watcher_opts.hook_windows = hook_windows;
 
     let mut manager = ShutdownManager::with_timeout(Duration::from_secs(1));
 
+    if let Some(target) = opts.lsp.clone() {
+        let (lsp_sender, lsp_receiver) = mpsc::channel(8);
+        ghci_opts.lsp_sender = Some(lsp_sender);
+        manager
+            .spawn("run_lsp", |handle| run_lsp(handle, target, lsp_receiver))
+            .await;
+    }
+
     if opts.tui {
         let tracing_reader =
             maybe_tracing_reader.expect("`tracing_reader` must be present if `tui` is given");
@@ -65,9 +88,31 @@ async fn main() -> miette::Result<()> {
             maybe_ghci_reader.expect("`tui_reader` must be present if `tui` is given");
         let actions = opts.tui_opts.get_actions();
         let action_sender = ghci_sender.clone();
+        let progress = ghci_opts
+            .progress
+            .as_ref()
+            .map(|progress| progress.subscribe());
+        let compile_times = ghci_opts.compile_times.clone();
         manager
             .spawn("run_tui", |handle| {
-                run_tui(handle, ghci_reader, tracing_reader, actions, action_sender)
+                run_tui(
+                    handle,
+                    ghci_reader,
+                    tracing_reader,
+                    actions,
+                    action_sender,
+                    progress,
+                    compile_times,
+                )
+            })
+            .await;
+    }
+
+    if let Some(path) = opts.control_socket.clone() {
+        let control_sender = ghci_sender.clone();
+        manager
+            .spawn("run_control_socket", |handle| {
+                run_control_socket(handle, path, control_sender)
             })
             .await;
     }
@@ -1,15 +1,32 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fmt::Display;
 use std::fmt::Write;
+use std::future::Future;
+use std::os::unix::process::ExitStatusExt;
+use std::pin::Pin;
 use std::process::ExitStatus;
+use std::process::Output;
 use std::process::Stdio;
 use std::str::FromStr;
+use std::time::Duration;
 
+use command_group::AsyncCommandGroup;
+use command_group::AsyncGroupChild;
 use miette::miette;
 use miette::Context;
 use miette::IntoDiagnostic;
+use nix::sys::signal;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::BufReader;
+use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 use tracing::instrument;
 use tracing::Instrument;
+use winnow::combinator::alt;
 use winnow::combinator::opt;
 use winnow::combinator::rest;
 use winnow::PResult;
@@ -23,8 +40,16 @@ use crate::command_ext::CommandExt;
 pub struct MaybeAsyncCommand {
     /// Should this command be run asynchronously?
     pub is_async: bool,
+    /// Should the previous instance of this command be killed before it's run again?
+    ///
+    /// Restartable commands are always run asynchronously.
+    pub is_restartable: bool,
     /// The contained command.
     pub command: ClonableCommand,
+    /// How long to let the command run before killing it.
+    pub timeout: Option<Duration>,
+    /// Should failures be logged at the debug level rather than as errors?
+    pub quiet: bool,
 }
 
 impl Display for MaybeAsyncCommand {
@@ -44,27 +69,139 @@ impl FromStr for MaybeAsyncCommand {
 }
 
 fn parse_maybe_async_command(input: &mut &str) -> PResult<MaybeAsyncCommand> {
-    let is_async = opt("async:").parse_next(input)?.is_some();
+    let prefix = opt(alt(("async:", "restart:"))).parse_next(input)?;
 
     let command = rest.parse_to().parse_next(input)?;
 
-    Ok(MaybeAsyncCommand { is_async, command })
+    Ok(MaybeAsyncCommand {
+        is_async: prefix.is_some(),
+        is_restartable: prefix == Some("restart:"),
+        command,
+        timeout: None,
+        quiet: false,
+    })
 }
 
 impl MaybeAsyncCommand {
+    /// Set environment variables for this command.
+    pub fn envs(
+        mut self,
+        vars: impl IntoIterator<Item = (impl Into<OsString>, impl Into<OsString>)>,
+    ) -> Self {
+        self.command = self.command.envs(vars);
+        self
+    }
+
+    /// Kill this command if it runs for longer than `timeout`.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Log failures of this command at the debug level rather than as errors.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
     #[instrument(skip(self), fields(%self), level = "debug")]
     pub async fn status(&self) -> MaybeAsyncCommandStatus {
         let program = self.command.program.to_string_lossy().into_owned();
         let mut command = self.command.as_tokio();
         let command_formatted = self.display();
+        let timeout = self.timeout;
+        let quiet = self.quiet;
+
+        // Commands never share our stdio: with `--lsp stdio`, `stdin` and `stdout` carry LSP
+        // messages. (`output()` would null `stdin` anyway, but `group_spawn()` inherits it.)
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Restartable and timed commands are run in their own process group so that we can kill
+        // all of their children when they're restarted or time out; killing `sh` alone would
+        // leave them running. The output resolves to `true` if the command was killed to restart
+        // or stop it.
+        let mut kill_sender = None;
+        let mut process_group = None;
+        let output: Pin<Box<dyn Future<Output = (std::io::Result<Output>, bool)> + Send>> =
+            if self.is_restartable || timeout.is_some() {
+                let child = match command
+                    .group_spawn()
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to execute `{command_formatted}`"))
+                {
+                    Ok(child) => child,
+                    Err(err) => {
+                        return MaybeAsyncCommandStatus::Sync(Err(err));
+                    }
+                };
+                let process_group_id = match child.id() {
+                    Some(id) => Pid::from_raw(id as i32),
+                    None => {
+                        return MaybeAsyncCommandStatus::Sync(Err(miette!(
+                            "`{command_formatted}` has no process group ID"
+                        )));
+                    }
+                };
+                process_group = Some(process_group_id);
+                let is_restartable = self.is_restartable;
+                let program = program.clone();
+                let receiver = if is_restartable {
+                    let (sender, receiver) = oneshot::channel::<()>();
+                    kill_sender = Some(sender);
+                    Some(receiver)
+                } else {
+                    None
+                };
+                Box::pin(async move {
+                    let mut output: Pin<Box<dyn Future<Output = _> + Send>> = if is_restartable {
+                        Box::pin(wait_logging_output(child, program))
+                    } else {
+                        Box::pin(child.wait_with_output())
+                    };
+                    let stopped = async move {
+                        match receiver {
+                            // Also fires if the sender is dropped.
+                            Some(receiver) => {
+                                let _ = receiver.await;
+                            }
+                            None => std::future::pending().await,
+                        }
+                    };
+                    tokio::select! {
+                        output = &mut output => (output, false),
+                        _ = stopped => {
+                            kill_process_group(process_group_id);
+                            (output.await, true)
+                        }
+                    }
+                })
+            } else {
+                Box::pin(async move { (command.output().await, false) })
+            };
+
         let join_handle = tokio::task::spawn(
             async move {
                 tracing::info!("$ {command_formatted}");
-                let output = command
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()
-                    .await
+                let (output, killed) = match timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, output).await {
+                        Ok(output) => output,
+                        Err(_) => {
+                            tracing::error!(
+                                "`{command_formatted}` timed out after {}",
+                                humantime::format_duration(timeout)
+                            );
+                            if let Some(process_group_id) = process_group {
+                                kill_process_group(process_group_id);
+                            }
+                            return Ok(ExitStatus::from_raw(Signal::SIGKILL as i32));
+                        }
+                    },
+                    None => output.await,
+                };
+                let output = output
                     .into_diagnostic()
                     .wrap_err_with(|| format!("Failed to execute `{command_formatted}`"))?;
 
@@ -72,7 +209,9 @@ impl MaybeAsyncCommand {
 
                 let mut message = shell_words::quote(&program).into_owned();
                 message.push(' ');
-                if status.success() {
+                if killed {
+                    message.push_str("was stopped");
+                } else if status.success() {
                     message.push_str("finished successfully");
                 } else {
                     write!(message, "failed: {status}").expect("Writing to a `String` never fails");
@@ -92,7 +231,7 @@ impl MaybeAsyncCommand {
                         .expect("Writing to a `String` never fails");
                 }
 
-                if status.success() {
+                if status.success() || quiet || killed {
                     tracing::debug!("{message}");
                 } else {
                     tracing::error!("{message}");
@@ -103,7 +242,12 @@ impl MaybeAsyncCommand {
             .instrument(tracing::debug_span!("status").or_current()),
         );
 
-        if self.is_async {
+        if let Some(kill_sender) = kill_sender {
+            MaybeAsyncCommandStatus::Restartable(RestartableCommand {
+                join_handle,
+                kill_sender,
+            })
+        } else if self.is_async {
             MaybeAsyncCommandStatus::Async(join_handle)
         } else {
             let command_formatted = self.display();
@@ -118,31 +262,133 @@ impl MaybeAsyncCommand {
 
     /// Run this command.
     ///
-    /// If it's a synchronous command, report its status. Otherwise, add the [`JoinHandle`] for its
-    /// task to the given list of handles.
-    pub async fn run_on(
-        &self,
-        handles: &mut Vec<JoinHandle<miette::Result<ExitStatus>>>,
-    ) -> miette::Result<()> {
+    /// If it's a synchronous command, report its status and return it. Otherwise, add the
+    /// [`JoinHandle`] for its task to the given handles and return `None`.
+    ///
+    /// If it's a restartable command, the previous instance of the command is stopped first.
+    pub async fn run_on(&self, handles: &mut CommandHandles) -> miette::Result<Option<ExitStatus>> {
+        let key = self.display();
+        if self.is_restartable {
+            if let Some(previous) = handles.restartable.remove(&key) {
+                tracing::info!("Restarting `{key}`");
+                previous.stop().await;
+            }
+        }
+
         match self.status().await {
             MaybeAsyncCommandStatus::Sync(result) => {
                 // If we failed to execute the program, that's an actual error, but if the
                 // program failed on its own, we'll log and move on.
-                result?;
+                Ok(Some(result?))
             }
             MaybeAsyncCommandStatus::Async(join_handle) => {
                 // If the program is running asynchronously, we'll store the `JoinHandle`
                 // so we don't kill it and so we can log when it completes.
-                handles.push(join_handle);
+                handles.handles.push(join_handle);
+                Ok(None)
+            }
+            MaybeAsyncCommandStatus::Restartable(command) => {
+                handles.restartable.insert(key, command);
+                Ok(None)
             }
         }
-        Ok(())
+    }
+}
+
+/// Wait for a restartable command to exit, logging its output as it's printed.
+///
+/// Restartable commands are often long-running servers, so their output isn't collected; it's
+/// logged rather than inherited because `stdout` may be carrying LSP messages.
+async fn wait_logging_output(
+    mut child: AsyncGroupChild,
+    program: String,
+) -> std::io::Result<Output> {
+    let stdout = child.inner().stdout.take();
+    let stderr = child.inner().stderr.take();
+    let (status, (), ()) = tokio::join!(
+        child.wait(),
+        log_lines(stdout, &program),
+        log_lines(stderr, &program)
+    );
+    Ok(Output {
+        status: status?,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    })
+}
+
+/// Log each line read from a command's output.
+async fn log_lines(reader: Option<impl AsyncRead + Unpin>, program: &str) {
+    let mut lines = match reader {
+        Some(reader) => BufReader::new(reader).lines(),
+        None => return,
+    };
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => tracing::info!("{program}: {line}"),
+            Ok(None) => break,
+            Err(err) => {
+                tracing::debug!("Failed to read output from `{program}`: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// Kill every process in a command's process group.
+fn kill_process_group(process_group_id: Pid) {
+    if let Err(err) = signal::killpg(process_group_id, Signal::SIGKILL) {
+        tracing::debug!("Failed to kill process group {process_group_id}: {err}");
     }
 }
 
 pub enum MaybeAsyncCommandStatus {
     Sync(miette::Result<ExitStatus>),
     Async(JoinHandle<miette::Result<ExitStatus>>),
+    Restartable(RestartableCommand),
+}
+
+/// A restartable command running in the background.
+#[derive(Debug)]
+pub struct RestartableCommand {
+    join_handle: JoinHandle<miette::Result<ExitStatus>>,
+    /// Tells the command's task to kill its process group.
+    kill_sender: oneshot::Sender<()>,
+}
+
+impl RestartableCommand {
+    /// Kill this command's process group and wait for it to exit.
+    async fn stop(self) {
+        let _ = self.kill_sender.send(());
+        if let Err(err) = self.join_handle.await {
+            tracing::debug!("Failed to wait for restartable command: {err}");
+        }
+    }
+}
+
+/// Handles for shell commands running in the background.
+#[derive(Debug, Default)]
+pub struct CommandHandles {
+    /// Asynchronous commands.
+    handles: Vec<JoinHandle<miette::Result<ExitStatus>>>,
+    /// Restartable commands, keyed by the command being run.
+    restartable: HashMap<String, RestartableCommand>,
+}
+
+impl CommandHandles {
+    /// Get rid of any handles for background commands that have finished.
+    pub fn prune(&mut self) {
+        self.handles.retain(|handle| !handle.is_finished());
+        self.restartable
+            .retain(|_, command| !command.join_handle.is_finished());
+    }
+
+    /// Stop all the restartable commands and wait for them to exit.
+    pub async fn stop_restartable(&mut self) {
+        for (_, command) in self.restartable.drain() {
+            command.stop().await;
+        }
+    }
 }
 
 impl CommandExt for MaybeAsyncCommand {
@@ -163,8 +409,11 @@ mod tests {
                 .unwrap(),
             MaybeAsyncCommand {
                 is_async: false,
+                is_restartable: false,
                 command: ClonableCommand::new("puppy")
-                    .args(["--flavor", "sammy", "--eyes", "brown"])
+                    .args(["--flavor", "sammy", "--eyes", "brown"]),
+                timeout: None,
+                quiet: false,
             }
         );
 
@@ -174,9 +423,67 @@ mod tests {
                 .unwrap(),
             MaybeAsyncCommand {
                 is_async: true,
+                is_restartable: false,
                 command: ClonableCommand::new("puppy")
-                    .args(["--flavor", "sammy", "--eyes", "brown"])
+                    .args(["--flavor", "sammy", "--eyes", "brown"]),
+                timeout: None,
+                quiet: false,
             }
         );
+
+        assert_eq!(
+            "restart: ./run-server --port 8080"
+                .parse::<MaybeAsyncCommand>()
+                .unwrap(),
+            MaybeAsyncCommand {
+                is_async: true,
+                is_restartable: true,
+                command: ClonableCommand::new("./run-server").args(["--port", "8080"]),
+                timeout: None,
+                quiet: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let command = format!(
+            "sh -c '(sleep 1 && touch {}) & wait'",
+            shell_words::quote(&marker.to_string_lossy())
+        )
+        .parse::<MaybeAsyncCommand>()
+        .unwrap()
+        .timeout(Some(Duration::from_millis(100)));
+
+        match command.status().await {
+            MaybeAsyncCommandStatus::Sync(status) => {
+                assert_eq!(status.unwrap().signal(), Some(Signal::SIGKILL as i32));
+            }
+            _ => panic!("Expected a synchronous command"),
+        }
+
+        // The background `sleep` was killed along with `sh`.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_restartable_output_is_logged() {
+        let command = "restart:sh -c 'echo puppy && sleep 30'"
+            .parse::<MaybeAsyncCommand>()
+            .unwrap();
+
+        let command = match command.status().await {
+            MaybeAsyncCommandStatus::Restartable(command) => command,
+            _ => panic!("Expected a restartable command"),
+        };
+
+        // The output is logged while the command is still running.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(logs_contain("sh: puppy"));
+        command.stop().await;
     }
 }
@@ -1,5 +1,6 @@
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::time::Duration;
 
 use ansi_to_tui::IntoText;
 use crossterm::event::Event;
@@ -13,6 +14,7 @@ use ratatui::prelude::Buffer;
 use ratatui::prelude::Constraint;
 use ratatui::prelude::Layout;
 use ratatui::prelude::Rect;
+use ratatui::widgets::Gauge;
 use ratatui::widgets::Paragraph;
 use ratatui::widgets::Widget;
 use ratatui::widgets::Wrap;
@@ -20,6 +22,7 @@ use saturating::Saturating;
 use tokio::io::AsyncBufReadExt;
 use tokio::io::BufReader;
 use tokio::io::DuplexStream;
+use tokio::sync::watch;
 use tokio_stream::StreamExt;
 use tracing::instrument;
 
@@ -27,7 +30,10 @@ mod terminal;
 
 use crate::buffers::TUI_SCROLLBACK_CAPACITY;
 use crate::cli::{TuiAction, TuiActionCommand};
+use crate::ghci::compile_times::CompileTimes;
+use crate::ghci::compile_times::SLOWEST_MODULES_COUNT;
 use crate::ghci::manager::{InternalCommand, WatcherEvent};
+use crate::ghci::progress::CompilationProgress;
 use crate::ShutdownHandle;
 use terminal::TerminalGuard;
 
@@ -42,6 +48,8 @@ struct TuiState {
     actions: Vec<TuiAction>,
     show_actions: bool,
     quit_confirm: bool,
+    /// Progress compiling modules, if any are being compiled.
+    progress: Option<CompilationProgress>,
 }
 
 impl TuiState {
@@ -55,6 +63,7 @@ impl TuiState {
             actions,
             show_actions: true,
             quit_confirm: false,
+            progress: None,
         }
     }
 
@@ -73,6 +82,7 @@ impl TuiState {
 
         let areas = Layout::vertical([
             Constraint::Fill(1),
+            Constraint::Length(if self.progress.is_some() { 1 } else { 0 }),
             Constraint::Length(action_height),
             Constraint::Length(if self.debug { 1 } else { 0 }),
         ])
@@ -89,6 +99,13 @@ impl TuiState {
             .scroll((scroll_offset, 0))
             .render(areas[0], buffer);
 
+        if let Some(progress) = &self.progress {
+            Gauge::default()
+                .ratio(progress.ratio())
+                .label(progress.to_string())
+                .render(areas[1], buffer);
+        }
+
         // Render actions as a horizontal row
         if self.show_actions && !self.actions.is_empty() {
             let action_text = if self.quit_confirm {
@@ -101,10 +118,10 @@ impl TuiState {
                     }
                     text.push_str(&format!("[{}] {}", i + 1, action.label));
                 }
-                text.push_str(" | [a] hide | [q] quit");
+                text.push_str(" | [e] eval all | [s] slowest modules | [a] hide | [q] quit");
                 text
             };
-            Paragraph::new(action_text).render(areas[1], buffer);
+            Paragraph::new(action_text).render(areas[2], buffer);
         }
 
         if self.debug {
@@ -113,7 +130,7 @@ impl TuiState {
             Paragraph::new(format!(
                 "(☞ ﾟ ヮﾟ )☞  line_count={line_count}, scroll_offset={scroll_offset}"
             ))
-            .render(areas[2], buffer);
+            .render(areas[3], buffer);
         }
 
         Ok(())
@@ -126,6 +143,7 @@ struct Tui {
     size: Rect,
     state: TuiState,
     action_sender: tokio::sync::mpsc::Sender<WatcherEvent>,
+    compile_times: CompileTimes,
 }
 
 impl Deref for Tui {
@@ -147,6 +165,7 @@ impl Tui {
         mut terminal: TerminalGuard,
         actions: Vec<TuiAction>,
         action_sender: tokio::sync::mpsc::Sender<WatcherEvent>,
+        compile_times: CompileTimes,
     ) -> Self {
         let area = terminal.get_frame().size();
         Self {
@@ -154,6 +173,7 @@ impl Tui {
             size: area,
             state: TuiState::new(actions),
             action_sender,
+            compile_times,
         }
     }
 
@@ -256,6 +276,20 @@ impl Tui {
                     // Any other key cancels quit
                     self.quit_confirm = false;
                 }
+                (KeyModifiers::NONE, KeyCode::Char('s')) => {
+                    let report = self
+                        .compile_times
+                        .report(SLOWEST_MODULES_COUNT)
+                        .unwrap_or_else(|| "No modules have been compiled yet".to_owned());
+                    for line in report.lines() {
+                        self.push_line(line.to_owned());
+                    }
+                    self.scroll_to(usize::MAX);
+                }
+                (KeyModifiers::NONE, KeyCode::Char('e')) => {
+                    self.trigger_action(TuiActionCommand::Internal("eval-all".to_owned()))
+                        .await?;
+                }
                 (KeyModifiers::NONE, KeyCode::Char(c @ '1'..='9')) => {
                     self.quit_confirm = false; // Cancel quit on action trigger
                     let index = (c as usize) - ('1' as usize);
@@ -282,6 +316,7 @@ impl Tui {
                 let internal_cmd = match cmd.as_str() {
                     "toggle-track-warnings" => InternalCommand::ToggleTrackWarnings,
                     "toggle-no-load" => InternalCommand::ToggleNoLoad,
+                    "eval-all" => InternalCommand::EvalAll,
                     _ => {
                         tracing::error!(%cmd, "Unknown internal command");
                         return Err(miette::miette!("Unknown internal command: {}", cmd));
@@ -305,12 +340,14 @@ pub async fn run_tui(
     tracing_reader: DuplexStream,
     actions: Vec<TuiAction>,
     action_sender: tokio::sync::mpsc::Sender<WatcherEvent>,
+    mut progress: Option<watch::Receiver<Option<CompilationProgress>>>,
+    compile_times: CompileTimes,
 ) -> miette::Result<()> {
     let mut ghci_reader = BufReader::new(ghci_reader).lines();
     let mut tracing_reader = BufReader::new(tracing_reader).lines();
 
     let terminal = terminal::enter()?;
-    let mut tui = Tui::new(terminal, actions, action_sender);
+    let mut tui = Tui::new(terminal, actions, action_sender, compile_times);
 
     let mut event_stream = EventStream::new();
 
@@ -336,6 +373,20 @@ pub async fn run_tui(
                 }
             }
 
+            Some(()) = async {
+                match &mut progress {
+                    Some(progress) => progress.changed().await.ok(),
+                    None => std::future::pending().await,
+                }
+            } => {
+                tui.progress = progress
+                    .as_ref()
+                    .and_then(|progress| progress.borrow().clone());
+            }
+
+            // Redraw to update the elapsed time while compiling.
+            _ = tokio::time::sleep(Duration::from_secs(1)), if tui.progress.is_some() => {}
+
             line = tracing_reader.next_line() => {
                 let line = line.into_diagnostic().wrap_err("Failed to read line from tracing")?;
                 if let Some(line) = line {
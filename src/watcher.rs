@@ -18,6 +18,7 @@ use tracing::instrument;
 use crate::cli::Opts;
 use crate::event_filter::file_events_from_action;
 use crate::ghci::manager::WatcherEvent;
+use crate::hook_windows::HookWindows;
 use crate::normal_path::NormalPath;
 use crate::shutdown::ShutdownHandle;
 
@@ -30,6 +31,8 @@ pub struct WatcherOpts {
     pub debounce: Duration,
     /// If given, use the polling file watcher with the given duration as the poll interval.
     pub poll: Option<Duration>,
+    /// If given, ignore events which happen while a shell hook is running.
+    pub hook_windows: Option<HookWindows>,
 }
 
 impl WatcherOpts {
@@ -42,6 +45,7 @@ impl WatcherOpts {
             watch: opts.watch.paths.clone(),
             debounce: opts.watch.debounce,
             poll: opts.watch.poll,
+            hook_windows: None,
         }
     }
 }
@@ -75,6 +79,7 @@ async fn run_debouncer<T: notify::Watcher>(
         handle: Handle::current(),
         ghci_sender,
         shutdown: handle.clone(),
+        hook_windows: opts.hook_windows.clone(),
     };
 
     let cache = FileIdMap::new();
@@ -124,6 +129,7 @@ struct EventHandler {
     handle: Handle,
     ghci_sender: mpsc::Sender<WatcherEvent>,
     shutdown: ShutdownHandle,
+    hook_windows: Option<HookWindows>,
 }
 
 impl EventHandler {
@@ -148,6 +154,27 @@ impl EventHandler {
 
         tracing::trace!(?events, "Got events");
 
+        let events = match &self.hook_windows {
+            Some(windows) => events
+                .into_iter()
+                .filter(|event| {
+                    // Renames have several paths; only ignore the event if a hook wrote all of
+                    // them.
+                    let caused_by_hook = !event.event.paths.is_empty()
+                        && event
+                            .event
+                            .paths
+                            .iter()
+                            .all(|path| windows.contains(event.time, path));
+                    if caused_by_hook {
+                        tracing::debug!(paths = ?event.event.paths, "Ignoring event caused by a hook");
+                    }
+                    !caused_by_hook
+                })
+                .collect(),
+            None => events,
+        };
+
         // TODO: On Linux, sometimes we get a "new directory" event but none of the events for
         // files inside of it. When we get new directories, we should paw through them with
         // `walkdir` or something to check for files.